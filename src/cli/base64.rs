@@ -2,16 +2,17 @@ use super::verify_file;
 use anyhow::Result;
 use clap::Parser;
 use std::fmt;
+use std::io::BufWriter;
 use std::str::FromStr;
 
-use crate::{get_reader, CmdExecutor};
+use crate::{get_reader, get_writer, CmdExecutor};
 
 #[derive(Debug, Parser)]
 #[enum_dispatch::enum_dispatch(CmdExecutor)]
 pub enum Base64SubCommand {
-    #[command(name = "encode", about = "Encode a string to base64")]
+    #[command(name = "encode", about = "Encode a file or stdin to base64")]
     Encode(Base64EncodeOpts),
-    #[command(name = "decode", about = "Decode a base64 string to plain text")]
+    #[command(name = "decode", about = "Decode base64 to raw bytes")]
     Decode(Base64DecodeOpts),
 }
 
@@ -20,6 +21,9 @@ pub struct Base64EncodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
     #[arg(long, value_parser = parse_base64_format, default_value = "standard")]
     pub format: Base64Format,
 }
@@ -29,6 +33,10 @@ pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
+    /// Where to write the decoded bytes, which may be binary
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
     #[arg(long, value_parser = parse_base64_format, default_value = "standard")]
     pub format: Base64Format,
 }
@@ -36,8 +44,8 @@ pub struct Base64DecodeOpts {
 impl CmdExecutor for Base64EncodeOpts {
     async fn execute(self) -> Result<()> {
         let mut reader = get_reader(&self.input)?;
-        let ret = crate::process_encode(&mut reader, self.format)?;
-        println!("{}", ret);
+        let mut writer = BufWriter::new(get_writer(&self.output)?);
+        crate::process_encode(&mut reader, &mut writer, self.format)?;
         Ok(())
     }
 }
@@ -45,8 +53,8 @@ impl CmdExecutor for Base64EncodeOpts {
 impl CmdExecutor for Base64DecodeOpts {
     async fn execute(self) -> Result<()> {
         let mut reader = get_reader(&self.input)?;
-        let ret = crate::process_decode(&mut reader, self.format)?;
-        println!("{}", ret);
+        let mut writer = BufWriter::new(get_writer(&self.output)?);
+        crate::process_decode(&mut reader, &mut writer, self.format)?;
         Ok(())
     }
 }
@@ -0,0 +1,29 @@
+use crate::{process_breach_build, CmdExecutor};
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum BreachSubCommand {
+    #[command(about = "Build a breach db index from HIBP-style SHA-1 dumps")]
+    Build(BreachBuildOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct BreachBuildOpts {
+    /// `HASH:COUNT` text files, or directories of range files named by hash prefix
+    #[arg(required = true)]
+    pub inputs: Vec<String>,
+
+    /// Directory to write the index to
+    #[arg(short, long)]
+    pub output: String,
+}
+
+impl CmdExecutor for BreachBuildOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let count = process_breach_build(&self.inputs, &self.output)?;
+        eprintln!("Indexed {} hashes into {}", count, self.output);
+        Ok(())
+    }
+}
@@ -1,53 +1,615 @@
-use super::verify_file;
-use crate::{process_csv, CmdExecutor};
-use clap::Parser;
+use super::{exit_on_error, verify_file};
+use crate::{
+    process_csv, process_csv_cat, process_csv_dedupe, process_csv_diff, process_csv_import,
+    process_csv_join, process_csv_query, process_csv_show, process_csv_split, process_csv_stats,
+    process_csv_validate, write_diff, write_stats, write_validation, CmdExecutor, Predicate,
+    RowWindow, SortKey, TableLayout,
+};
+use clap::{ArgAction, Args, Parser};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
 use std::fmt;
+use std::io::{self, IsTerminal, Write};
 use std::str::FromStr;
 
+/// `rcli csv -i input.csv` converts a file, `rcli csv <COMMAND>` runs one of the other tools.
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file)]
-    pub input: String,
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[command(flatten)]
+    pub convert: CsvConvertOpts,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExecutor)]
+pub enum CsvSubCommand {
+    #[command(
+        name = "import",
+        about = "Convert JSON, YAML or NDJSON records into CSV"
+    )]
+    Import(CsvImportOpts),
+    #[command(name = "show", about = "Show a CSV file as a table")]
+    Show(CsvShowOpts),
+    #[command(name = "stats", about = "Profile the columns of a CSV file")]
+    Stats(CsvStatsOpts),
+    #[command(name = "diff", about = "Compare two CSV files keyed by columns")]
+    Diff(CsvDiffOpts),
+    #[command(name = "join", about = "Join two CSV files on key columns")]
+    Join(CsvJoinOpts),
+    #[command(name = "cat", about = "Concatenate CSV files, merging their headers")]
+    Cat(CsvCatOpts),
+    #[command(name = "dedupe", about = "Drop duplicate rows from a CSV file")]
+    Dedupe(CsvDedupeOpts),
+    #[command(name = "query", about = "Run a SQL query over CSV files")]
+    Query(CsvQueryOpts),
+    #[command(name = "split", about = "Split a CSV file into chunks")]
+    Split(CsvSplitOpts),
+    #[command(name = "validate", about = "Check a CSV file against a schema")]
+    Validate(CsvValidateOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvConvertOpts {
+    // optional only so that subcommands can be used without it, see `CsvOpts`
+    #[arg(short, long, value_parser = verify_file, required = true)]
+    pub input: Option<String>,
 
     #[arg(short, long)] // default_value = "output.json"
     pub output: Option<String>,
 
+    /// Output format: json, yaml, toml, ndjson, markdown or html
     #[arg(long, value_parser = parse_format, default_value = "json")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub shape: CsvShapeOpts,
+
+    #[command(flatten)]
+    pub types: CsvTypeOpts,
+
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+}
+
+/// Options controlling the shape of the converted records.
+#[derive(Debug, Clone, Args)]
+pub struct CsvShapeOpts {
+    /// How to emit rows of a file without a header row
+    #[arg(long, value_parser = parse_headerless_mode, default_value = "named")]
+    pub headerless: HeaderlessMode,
+
+    /// Build nested objects from dotted headers like `address.city`, and arrays from `tags[0]`
+    #[arg(long)]
+    pub nest: bool,
+}
+
+/// Options controlling how CSV input is parsed, shared by the csv commands.
+#[derive(Debug, Clone, Args)]
+pub struct CsvReaderOpts {
+    /// Field delimiter, a single ASCII character or `\t`
+    #[arg(short, long, value_parser = parse_delimiter, default_value = ",")]
+    pub delimiter: u8,
 
-    #[arg(long, default_value_t = true)]
+    /// Whether the first row is a header row, e.g. `--header false`
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub header: bool,
+
+    /// Input encoding, e.g. `utf-16le` or `windows-1252`; a byte order mark takes precedence
+    #[arg(long, value_parser = parse_encoding)]
+    pub encoding: Option<&'static Encoding>,
+}
+
+/// Options controlling how cell values are typed.
+#[derive(Debug, Clone, Args)]
+pub struct CsvTypeOpts {
+    /// Emit numbers, booleans and nulls (empty cells) as typed values instead of strings
+    #[arg(long, default_value_t = false)]
+    pub infer_types: bool,
+
+    /// Also recognize ISO 8601 dates when inferring types
+    #[arg(long, default_value_t = false, requires = "infer_types")]
+    pub infer_dates: bool,
+
+    /// YAML file pinning column types, e.g. `Kit Number: integer`
+    #[arg(long, value_parser = verify_file)]
+    pub schema: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvImportOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "output.csv")]
+    pub output: String,
+
+    /// Input format: json, yaml or ndjson
+    #[arg(long, value_parser = parse_format, default_value = "json")]
+    pub format: OutputFormat,
+
+    /// Comma separated fields to keep, in output order, e.g. `name,address.city`
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Vec<String>,
+
+    /// How to flatten arrays: join the items into one cell or explode them into rows
+    #[arg(long, value_parser = parse_array_mode, default_value = "join")]
+    pub arrays: ArrayMode,
+
+    /// Separator used when joining array items
+    #[arg(long, default_value = ";")]
+    pub array_separator: String,
+
+    /// Output field delimiter
+    #[arg(short, long, value_parser = parse_delimiter, default_value = ",")]
+    pub delimiter: u8,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvShowOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    /// Show only the first N rows
+    #[arg(long, conflicts_with = "tail")]
+    pub head: Option<usize>,
+
+    /// Show only the last N rows
+    #[arg(long)]
+    pub tail: Option<usize>,
+
+    /// Truncate cells wider than this
+    #[arg(long, default_value_t = 40)]
+    pub max_width: usize,
+
+    #[arg(long, default_value_t = false)]
+    pub no_row_numbers: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    /// Number of most frequent values to report per column
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+
+    /// Output format instead of a table: json, yaml, toml, ndjson, markdown or html
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    /// Comma separated key columns identifying a row, e.g. `Name`
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub key: Vec<String>,
+
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    /// Output format instead of a report: json or yaml
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    /// Comma separated key columns present in both files, e.g. `Name`
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub on: Vec<String>,
+
+    #[arg(value_parser = verify_file)]
+    pub left: String,
+
+    #[arg(value_parser = verify_file)]
+    pub right: String,
+
+    /// Join kind: inner, left or outer
+    #[arg(long, value_parser = parse_join_kind, default_value = "inner")]
+    pub how: JoinKind,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvCatOpts {
+    #[arg(value_parser = verify_file, required = true)]
+    pub inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDedupeOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    /// Comma separated columns identifying a duplicate, defaults to the whole row
+    #[arg(long, value_delimiter = ',')]
+    pub by: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    /// A SELECT statement, e.g. "SELECT Position, count(*) FROM juventus GROUP BY Position"
+    pub sql: String,
+
+    /// Table files as `name=path`, or a path named after its file stem; repeatable
+    #[arg(short, long)]
+    pub table: Vec<String>,
+
+    /// Output format: json, yaml, toml, ndjson, markdown or html
+    #[arg(long, value_parser = parse_format, default_value = "json")]
+    pub format: OutputFormat,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    /// Number of rows per chunk
+    #[arg(long, required_unless_present = "by", conflicts_with = "by")]
+    pub rows: Option<usize>,
+
+    /// Write one file per distinct value of this column instead
+    #[arg(long)]
+    pub by: Option<String>,
+
+    /// Directory for the chunks
+    #[arg(short, long, default_value = ".")]
+    pub output_dir: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file)]
+    pub input: String,
+
+    /// YAML schema of column rules: type, required, unique, pattern, enum, min, max
+    #[arg(long, value_parser = verify_file)]
+    pub schema: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    /// Output format instead of a report: json or yaml
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+}
+
+/// Options trimming the rows and columns of a CSV file before it is written out.
+#[derive(Debug, Clone, Args)]
+pub struct CsvFilterOpts {
+    /// Comma separated columns to keep, in output order
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+
+    /// Keep rows matching a condition, e.g. "Kit Number>10" or "Name~^G" (regex), repeatable
+    #[arg(long = "where", value_parser = parse_predicate)]
+    pub filter: Vec<Predicate>,
+
+    /// Sort by columns, e.g. "Position,Kit Number:desc"
+    #[arg(long, value_delimiter = ',', value_parser = parse_sort_key)]
+    pub sort_by: Vec<SortKey>,
 }
 
 impl CmdExecutor for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.convert.execute().await,
+        }
+    }
+}
+
+impl CmdExecutor for CsvConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let input = self
+            .input
+            .ok_or_else(|| anyhow::anyhow!("--input is required"))?;
         let output = if let Some(output) = self.output {
             output.clone()
         } else {
             format!("output.{}", self.format)
         };
-        process_csv(&self.input, output, self.format)
+        process_csv(
+            &input,
+            output,
+            self.format,
+            &self.reader,
+            &self.shape,
+            &self.types,
+            &self.filter,
+        )
+    }
+}
+
+impl CmdExecutor for CsvImportOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_import(
+            &self.input,
+            &self.output,
+            self.format,
+            &self.columns,
+            self.arrays,
+            &self.array_separator,
+            self.delimiter,
+        )
+    }
+}
+
+impl CmdExecutor for CsvShowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let window = match (self.head, self.tail) {
+            (Some(n), _) => RowWindow::Head(n),
+            (_, Some(n)) => RowWindow::Tail(n),
+            _ => RowWindow::All,
+        };
+        let stdout = io::stdout();
+        // fit the terminal, but leave long lines alone for pagers and pipes
+        let max_width = if stdout.is_terminal() {
+            term_size::dimensions_stdout().map(|(w, _)| w)
+        } else {
+            None
+        };
+        let layout = TableLayout {
+            max_cell_width: self.max_width,
+            max_width,
+        };
+        let mut out = io::BufWriter::new(stdout.lock());
+        let ret = process_csv_show(
+            &self.input,
+            &self.reader,
+            window,
+            !self.no_row_numbers,
+            &layout,
+            &mut out,
+        );
+        ignore_broken_pipe(ret.and_then(|_| Ok(out.flush()?)))
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+impl CmdExecutor for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let stats = process_csv_stats(&self.input, &self.reader, self.top)?;
+        let mut out = io::BufWriter::new(io::stdout().lock());
+        ignore_broken_pipe(write_stats(&stats, self.format, &mut out))
+    }
+}
+
+impl CmdExecutor for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // like diff(1), exit with 1 when the files differ and 2 on errors
+        let differ = exit_on_error((|| {
+            let diff = process_csv_diff(&self.old, &self.new, &self.key, &self.reader)?;
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            ignore_broken_pipe(write_diff(&diff, self.format, &mut out))?;
+            Ok(!diff.is_empty())
+        })());
+        if differ {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        ignore_broken_pipe(process_csv_join(
+            &self.left,
+            &self.right,
+            &self.on,
+            self.how,
+            &self.output,
+            &self.reader,
+        ))
+    }
+}
+
+impl CmdExecutor for CsvCatOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        ignore_broken_pipe(process_csv_cat(&self.inputs, &self.output, &self.reader))
+    }
+}
+
+impl CmdExecutor for CsvDedupeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        ignore_broken_pipe(process_csv_dedupe(
+            &self.input,
+            &self.by,
+            &self.output,
+            &self.reader,
+        ))
+    }
+}
+
+impl CmdExecutor for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        ignore_broken_pipe(process_csv_query(
+            &self.sql,
+            &self.table,
+            self.format,
+            &self.output,
+            &self.reader,
+        ))
+    }
+}
+
+impl CmdExecutor for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let files = process_csv_split(
+            &self.input,
+            self.rows,
+            self.by.as_deref(),
+            &self.output_dir,
+            &self.reader,
+        )?;
+        for file in files {
+            println!("{}", file.display());
+        }
+        Ok(())
+    }
+}
+
+impl CmdExecutor for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // exit with 1 when the file is invalid and 2 on errors
+        let valid = exit_on_error((|| {
+            let report = process_csv_validate(&self.input, &self.schema, &self.reader)?;
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            ignore_broken_pipe(write_validation(&report, self.format, &mut out))?;
+            Ok(report.is_valid())
+        })());
+        if !valid {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
+
+/// Treats a closed stdout (e.g. `rcli csv show ... | head`) as success.
+fn ignore_broken_pipe(ret: anyhow::Result<()>) -> anyhow::Result<()> {
+    match ret {
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        ret => ret,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum OutputFormat {
     Json,
     Yaml,
+    Toml,
+    Ndjson,
+    Markdown,
+    Html,
+}
+
+/// Row layout for files read with `--header false`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HeaderlessMode {
+    /// Objects keyed by generated column names: `col_0`, `col_1`, ...
+    Named,
+    /// Plain arrays of cell values
+    Array,
+}
+
+/// How arrays are flattened when converting records into CSV.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArrayMode {
+    /// Join the items into a single cell
+    Join,
+    /// Emit one row per item
+    Explode,
+}
+
+/// Which unmatched rows a `csv join` keeps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only rows with a match in both files
+    Inner,
+    /// Every left row, with empty right columns when unmatched
+    Left,
+    /// Every row of both files
+    Outer,
 }
 
-fn parse_format(format: &str) -> anyhow::Result<OutputFormat, anyhow::Error> {
+pub(crate) fn parse_format(format: &str) -> anyhow::Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
 
+fn parse_headerless_mode(mode: &str) -> anyhow::Result<HeaderlessMode, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_array_mode(mode: &str) -> anyhow::Result<ArrayMode, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_join_kind(kind: &str) -> anyhow::Result<JoinKind, anyhow::Error> {
+    kind.parse()
+}
+
+fn parse_predicate(predicate: &str) -> anyhow::Result<Predicate, anyhow::Error> {
+    predicate.parse()
+}
+
+fn parse_sort_key(key: &str) -> anyhow::Result<SortKey, anyhow::Error> {
+    key.parse()
+}
+
+fn parse_encoding(label: &str) -> anyhow::Result<&'static Encoding, anyhow::Error> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| anyhow::anyhow!("Unknown encoding {:?}", label))
+}
+
+fn parse_delimiter(delimiter: &str) -> anyhow::Result<u8, anyhow::Error> {
+    match delimiter {
+        "\\t" | "tab" => Ok(b'\t'),
+        s if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(anyhow::anyhow!(
+            "Delimiter must be a single ASCII character"
+        )),
+    }
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(f: OutputFormat) -> Self {
         match f {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
         }
     }
 }
@@ -59,6 +621,10 @@ impl FromStr for OutputFormat {
         match s {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
             _ => Err(anyhow::anyhow!("Invalid format")),
         }
     }
@@ -69,3 +635,152 @@ impl fmt::Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl From<HeaderlessMode> for &'static str {
+    fn from(m: HeaderlessMode) -> Self {
+        match m {
+            HeaderlessMode::Named => "named",
+            HeaderlessMode::Array => "array",
+        }
+    }
+}
+
+impl FromStr for HeaderlessMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "named" => Ok(HeaderlessMode::Named),
+            "array" => Ok(HeaderlessMode::Array),
+            _ => Err(anyhow::anyhow!("Invalid headerless mode")),
+        }
+    }
+}
+
+impl fmt::Display for HeaderlessMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<ArrayMode> for &'static str {
+    fn from(m: ArrayMode) -> Self {
+        match m {
+            ArrayMode::Join => "join",
+            ArrayMode::Explode => "explode",
+        }
+    }
+}
+
+impl FromStr for ArrayMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "join" => Ok(ArrayMode::Join),
+            "explode" => Ok(ArrayMode::Explode),
+            _ => Err(anyhow::anyhow!("Invalid array mode")),
+        }
+    }
+}
+
+impl fmt::Display for ArrayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(k: JoinKind) -> Self {
+        match k {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Outer => "outer",
+        }
+    }
+}
+
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "outer" | "full" => Ok(JoinKind::Outer),
+            _ => Err(anyhow::anyhow!("Invalid join kind")),
+        }
+    }
+}
+
+impl fmt::Display for JoinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(",").unwrap(), b',');
+        assert_eq!(parse_delimiter(";").unwrap(), b';');
+        assert_eq!(parse_delimiter("|").unwrap(), b'|');
+        assert_eq!(parse_delimiter("\\t").unwrap(), b'\t');
+        assert_eq!(parse_delimiter("\t").unwrap(), b'\t');
+        assert!(parse_delimiter("ab").is_err());
+        assert!(parse_delimiter("é").is_err());
+    }
+
+    #[test]
+    fn test_output_format_roundtrip() {
+        for name in ["json", "yaml", "toml", "ndjson", "md", "html"] {
+            let format: OutputFormat = name.parse().unwrap();
+            assert_eq!(format.to_string(), name);
+        }
+        assert_eq!(
+            "markdown".parse::<OutputFormat>().unwrap(),
+            OutputFormat::Markdown
+        );
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_header_flag() {
+        let opts = CsvConvertOpts::parse_from(["csv", "-i", "-"]);
+        assert!(opts.reader.header);
+        let opts = CsvConvertOpts::parse_from(["csv", "-i", "-", "--header", "false"]);
+        assert!(!opts.reader.header);
+        assert!(CsvConvertOpts::try_parse_from(["csv", "-i", "-", "--header"]).is_err());
+
+        // the value is never optional, so it cannot swallow a positional file
+        let file = "assets/juventus.csv";
+        let args = [
+            "csv", "diff", "--key", "Name", "--header", "false", file, file,
+        ];
+        match CsvOpts::parse_from(args).cmd {
+            Some(CsvSubCommand::Diff(diff)) => {
+                assert!(!diff.reader.header);
+                assert_eq!((diff.old.as_str(), diff.new.as_str()), (file, file));
+            }
+            cmd => panic!("unexpected command {:?}", cmd),
+        }
+        let args = ["csv", "diff", "--key", "Name", "--header", file, file];
+        assert!(CsvOpts::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_csv_subcommands() {
+        let opts = CsvOpts::parse_from(["csv", "-i", "-", "--format", "yaml"]);
+        assert!(opts.cmd.is_none());
+        assert_eq!(opts.convert.input.as_deref(), Some("-"));
+        let opts = CsvOpts::parse_from(["csv", "import", "--columns", "a,b.c"]);
+        match opts.cmd {
+            Some(CsvSubCommand::Import(import)) => assert_eq!(import.columns, ["a", "b.c"]),
+            cmd => panic!("unexpected command {:?}", cmd),
+        }
+        assert!(CsvOpts::try_parse_from(["csv"]).is_err());
+    }
+}
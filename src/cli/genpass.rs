@@ -1,11 +1,26 @@
-use crate::{process_genpass, CmdExecutor};
+use crate::{
+    generate_unbreached, load_policy, load_wordlist, process_genbytes, process_genpass_batch,
+    process_genpass_derive, process_genphrase, read_master_secret, write_passwords, BreachDb,
+    CmdExecutor, PassConfig, PassReport, Policy, AMBIGUOUS, MAX_BYTES, MAX_LENGTH,
+};
 use clap::Parser;
-use zxcvbn::zxcvbn;
+use std::{fmt, str::FromStr};
 
 #[derive(Debug, Parser)]
 pub struct GenPassOpts {
-    #[arg(short, long, default_value_t = 16)]
-    pub length: u8,
+    /// random, pronounceable syllables, or a numeric pin
+    #[arg(long, value_parser = parse_pass_mode, default_value = "random")]
+    pub mode: PassMode,
+
+    #[arg(short, long, value_parser = parse_length, default_value_t = 16)]
+    pub length: usize,
+
+    /// Generate this many raw random bytes from the OS instead, encoded with --encoding
+    #[arg(long, value_parser = parse_bytes, conflicts_with_all = ["words", "derive", "policy", "mode"])]
+    pub bytes: Option<usize>,
+
+    #[arg(long, value_parser = parse_secret_encoding, default_value = "hex", requires = "bytes")]
+    pub encoding: SecretEncoding,
 
     #[arg(long, default_value_t = false)]
     pub no_uppercase: bool,
@@ -18,22 +33,329 @@ pub struct GenPassOpts {
 
     #[arg(long, default_value_t = false)]
     pub no_symbol: bool,
+
+    /// Explicit alphabet to draw from instead of the character classes
+    #[arg(long)]
+    pub charset: Option<String>,
+
+    /// Characters to never use
+    #[arg(long, default_value = "")]
+    pub exclude: String,
+
+    /// Symbols to use instead of the default `!@#$%^&*_`
+    #[arg(long)]
+    pub symbols: Option<String>,
+
+    /// Easily confused characters left out of the classes
+    #[arg(long, default_value = AMBIGUOUS)]
+    pub ambiguous: String,
+
+    /// Allow easily confused characters like `0` and `O`
+    #[arg(long, conflicts_with = "ambiguous")]
+    pub allow_ambiguous: bool,
+
+    #[arg(long, default_value_t = 0)]
+    pub min_upper: usize,
+
+    #[arg(long, default_value_t = 0)]
+    pub min_lower: usize,
+
+    #[arg(long, default_value_t = 0)]
+    pub min_digits: usize,
+
+    #[arg(long, default_value_t = 0)]
+    pub min_symbols: usize,
+
+    /// Number of passwords to generate
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub count: usize,
+
+    /// plain, or json / csv with each password's strength score, crack time and entropy
+    #[arg(long, value_parser = parse_pass_format, default_value = "plain")]
+    pub output_format: PassFormat,
+
+    /// Named policy to generate by, overriding the options it sets
+    #[arg(long, conflicts_with = "words")]
+    pub policy: Option<String>,
+
+    /// YAML or TOML file of named policies
+    #[arg(long, default_value = "policies.yaml", requires = "policy")]
+    pub policy_file: String,
+
+    /// Reject passwords found in this breach db, built by `rcli breach build`
+    #[arg(long)]
+    pub breach_db: Option<String>,
+
+    /// Derive a stable password for --site from a master secret instead of a random one
+    #[arg(long, requires = "site", conflicts_with_all = ["words", "count", "breach_db"])]
+    pub derive: bool,
+
+    #[arg(long, requires = "derive")]
+    pub site: Option<String>,
+
+    #[arg(long, default_value = "", requires = "derive")]
+    pub user: String,
+
+    /// Bump to rotate a derived password
+    #[arg(long, default_value_t = 1, requires = "derive")]
+    pub counter: u32,
+
+    /// File holding the master secret, instead of a prompt
+    #[arg(long, requires = "derive")]
+    pub master_file: Option<String>,
+
+    /// Generate a passphrase of this many words instead
+    #[arg(long)]
+    pub words: Option<usize>,
+
+    /// Wordlist file, one word per line, instead of the embedded list
+    #[arg(long, requires = "words")]
+    pub wordlist: Option<String>,
+
+    /// Separator between the words of a passphrase
+    #[arg(long, default_value = "-", requires = "words")]
+    pub separator: String,
+
+    /// Capitalize each word of a passphrase
+    #[arg(long, requires = "words")]
+    pub capitalize: bool,
+
+    /// Append a random digit to one word of a passphrase
+    #[arg(long, requires = "words")]
+    pub digit: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PassMode {
+    /// Characters drawn uniformly from the enabled classes
+    Random,
+    /// Consonant-vowel syllables, some capitalized, with digits and symbols between them
+    Pronounceable,
+    /// Digits only, without trivial sequences
+    Pin,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SecretEncoding {
+    Hex,
+    Base64,
+    Base32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PassFormat {
+    Plain,
+    Json,
+    Csv,
 }
 
 impl CmdExecutor for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        println!("{:?}", self);
-        let pass = process_genpass(
-            self.length,
-            !self.no_uppercase,
-            !self.no_lowercase,
-            !self.no_number,
-            !self.no_symbol,
-        )?;
-        println!("{}", pass);
-
-        let estimate = zxcvbn(&pass, &[])?;
-        eprintln!("Password strength: {}", estimate.score());
+        let policy = match &self.policy {
+            Some(name) => Some(load_policy(&self.policy_file, name)?),
+            None => None,
+        };
+        let reports = self
+            .generate(policy.as_ref())
+            .map_err(|e| match &self.policy {
+                Some(name) => e.context(format!("Policy {}", name)),
+                None => e,
+            })?;
+        write_passwords(&reports, self.output_format, std::io::stdout().lock())?;
+
+        if let [report] = &reports[..] {
+            if self.output_format == PassFormat::Plain {
+                eprintln!(
+                    "Password strength: {}, entropy: {:.1} bits",
+                    report.score, report.entropy
+                );
+            }
+        }
         Ok(())
     }
 }
+
+impl GenPassOpts {
+    fn generate(&self, policy: Option<&Policy>) -> anyhow::Result<Vec<PassReport>> {
+        let db = self.breach_db.as_deref().map(BreachDb::open).transpose()?;
+        if let Some(bytes) = self.bytes {
+            return (0..self.count)
+                .map(|_| {
+                    generate_unbreached(db.as_ref(), || {
+                        PassReport::new(process_genbytes(bytes, self.encoding)?, bytes as f64 * 8.0)
+                    })
+                })
+                .collect();
+        }
+        if let Some(words) = self.words {
+            let list = load_wordlist(self.wordlist.as_deref())?;
+            let mut rng = rand::thread_rng();
+            return (0..self.count)
+                .map(|_| {
+                    generate_unbreached(db.as_ref(), || {
+                        let (phrase, entropy) = process_genphrase(
+                            &list,
+                            words,
+                            &self.separator,
+                            self.capitalize,
+                            self.digit,
+                            &mut rng,
+                        )?;
+                        PassReport::new(phrase, entropy)
+                    })
+                })
+                .collect();
+        }
+        if self.derive {
+            let master = read_master_secret(self.master_file.as_deref())?;
+            let site = self.site.as_deref().unwrap_or_default();
+            let report = process_genpass_derive(
+                &master,
+                site,
+                &self.user,
+                self.counter,
+                self.config(),
+                policy,
+            )?;
+            return Ok(vec![report]);
+        }
+        process_genpass_batch(self.config(), policy, db.as_ref(), self.count)
+    }
+
+    fn config(&self) -> PassConfig {
+        PassConfig {
+            mode: self.mode,
+            length: self.length,
+            upper: !self.no_uppercase,
+            lower: !self.no_lowercase,
+            number: !self.no_number,
+            symbol: !self.no_symbol,
+            symbols: self.symbols.clone(),
+            charset: self.charset.clone(),
+            exclude: self.exclude.clone(),
+            ambiguous: if self.allow_ambiguous {
+                String::new()
+            } else {
+                self.ambiguous.clone()
+            },
+            min_upper: self.min_upper,
+            min_lower: self.min_lower,
+            min_digits: self.min_digits,
+            min_symbols: self.min_symbols,
+        }
+    }
+}
+
+fn parse_length(length: &str) -> anyhow::Result<usize, anyhow::Error> {
+    parse_bounded(length, "Length", MAX_LENGTH)
+}
+
+fn parse_bytes(bytes: &str) -> anyhow::Result<usize, anyhow::Error> {
+    parse_bounded(bytes, "Byte count", MAX_BYTES)
+}
+
+fn parse_bounded(n: &str, name: &str, max: usize) -> anyhow::Result<usize, anyhow::Error> {
+    match n.parse() {
+        Ok(n) if (1..=max).contains(&n) => Ok(n),
+        _ => Err(anyhow::anyhow!("{} must be between 1 and {}", name, max)),
+    }
+}
+
+fn parse_secret_encoding(encoding: &str) -> anyhow::Result<SecretEncoding, anyhow::Error> {
+    encoding.parse()
+}
+
+fn parse_pass_mode(mode: &str) -> anyhow::Result<PassMode, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_pass_format(format: &str) -> anyhow::Result<PassFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl From<PassMode> for &'static str {
+    fn from(m: PassMode) -> Self {
+        match m {
+            PassMode::Random => "random",
+            PassMode::Pronounceable => "pronounceable",
+            PassMode::Pin => "pin",
+        }
+    }
+}
+
+impl FromStr for PassMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(PassMode::Random),
+            "pronounceable" => Ok(PassMode::Pronounceable),
+            "pin" => Ok(PassMode::Pin),
+            _ => Err(anyhow::anyhow!("Invalid mode")),
+        }
+    }
+}
+
+impl fmt::Display for PassMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<SecretEncoding> for &'static str {
+    fn from(e: SecretEncoding) -> Self {
+        match e {
+            SecretEncoding::Hex => "hex",
+            SecretEncoding::Base64 => "base64",
+            SecretEncoding::Base32 => "base32",
+        }
+    }
+}
+
+impl FromStr for SecretEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(SecretEncoding::Hex),
+            "base64" => Ok(SecretEncoding::Base64),
+            "base32" => Ok(SecretEncoding::Base32),
+            _ => Err(anyhow::anyhow!("Invalid encoding")),
+        }
+    }
+}
+
+impl fmt::Display for SecretEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<PassFormat> for &'static str {
+    fn from(f: PassFormat) -> Self {
+        match f {
+            PassFormat::Plain => "plain",
+            PassFormat::Json => "json",
+            PassFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for PassFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(PassFormat::Plain),
+            "json" => Ok(PassFormat::Json),
+            "csv" => Ok(PassFormat::Csv),
+            _ => Err(anyhow::anyhow!("Invalid format")),
+        }
+    }
+}
+
+impl fmt::Display for PassFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
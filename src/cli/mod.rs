@@ -1,11 +1,13 @@
 mod base64;
+mod breach;
 mod csv;
 mod genpass;
 mod http;
 mod jwt;
+mod passcheck;
 mod text;
 
-pub use self::{base64::*, csv::*, genpass::*, http::*, jwt::*, text::*};
+pub use self::{base64::*, breach::*, csv::*, genpass::*, http::*, jwt::*, passcheck::*, text::*};
 use std::path::{Path, PathBuf};
 
 use clap::Parser;
@@ -18,6 +20,8 @@ pub struct Opts {
     pub cmd: SubCommand,
 }
 
+// parsed once per run, so the size of the csv options does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExecutor)]
 pub enum SubCommand {
@@ -25,6 +29,10 @@ pub enum SubCommand {
     Csv(CsvOpts),
     #[command(name = "genpass", about = "Generate a random password.")]
     GenPass(GenPassOpts),
+    #[command(name = "passcheck", about = "Check the strength of passwords.")]
+    PassCheck(PassCheckOpts),
+    #[command(subcommand, about = "Breached password db")]
+    Breach(BreachSubCommand),
     #[command(subcommand, about = "Base64 encode/decode")]
     Base64(Base64SubCommand),
     #[command(subcommand, about = "Text sign/verify")]
@@ -43,6 +51,15 @@ fn verify_file(filename: &str) -> anyhow::Result<String, &'static str> {
     }
 }
 
+/// Exits with 2 on an error, for commands that exit with 1 to report a negative result,
+/// like diff(1) does.
+fn exit_on_error<T>(ret: anyhow::Result<T>) -> T {
+    ret.unwrap_or_else(|e| {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(2)
+    })
+}
+
 fn verify_path(path: &str) -> anyhow::Result<PathBuf, &'static str> {
     let p = Path::new(path);
     if p.exists() && p.is_dir() {
@@ -0,0 +1,47 @@
+use super::{exit_on_error, parse_format, verify_file};
+use crate::{process_passcheck, write_passcheck, CmdExecutor, OutputFormat};
+use clap::Parser;
+use std::io;
+
+#[derive(Debug, Parser)]
+pub struct PassCheckOpts {
+    /// Passwords one per line, or `-` for stdin; prompts without echo on a terminal
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    /// Words that make a password weaker, like names or the site, comma separated
+    #[arg(short, long, value_delimiter = ',')]
+    pub user_inputs: Vec<String>,
+
+    /// Breach db directory built by `rcli breach build`; breached passwords fail the check
+    #[arg(long)]
+    pub breach_db: Option<String>,
+
+    /// Exit with status 1 if any password scores lower, 0 to 4; errors exit with 2
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4))]
+    pub min_score: Option<u8>,
+
+    /// json or yaml instead of the text report
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+}
+
+impl CmdExecutor for PassCheckOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let checks = exit_on_error((|| {
+            let checks =
+                process_passcheck(&self.input, &self.user_inputs, self.breach_db.as_deref())?;
+            let mut out = io::BufWriter::new(io::stdout().lock());
+            write_passcheck(&checks, self.format, &mut out)?;
+            Ok(checks)
+        })());
+        let min = self.min_score.unwrap_or_default();
+        if checks
+            .iter()
+            .any(|c| c.score < min || c.breached.is_some_and(|n| n > 0))
+        {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-// rcli csv -i input.csv -o output.json --header -d ','
+// rcli csv -i input.csv -o output.json --header true -d ','
 // rcli genpass -l 16 --no-lowercase --no-symbol
 
 use clap::Parser;
@@ -8,7 +8,7 @@ use rcli::{CmdExecutor, Opts};
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let opts = Opts::parse();
-    println!("{:?}", opts);
+    tracing::debug!("{:?}", opts);
     opts.cmd.execute().await?;
 
     Ok(())
@@ -1,39 +1,74 @@
 use crate::Base64Format;
 use anyhow::Result;
-use std::io::Read;
-use std::string::String;
+use std::io::{self, Read, Write};
 
 use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
-    Engine as _,
+    engine::general_purpose::{GeneralPurpose, STANDARD, URL_SAFE_NO_PAD},
+    read::DecoderReader,
+    write::EncoderWriter,
 };
 
-pub fn process_encode(reader: &mut dyn Read, format: Base64Format) -> Result<String> {
-    // let mut reader = get_reader(input)?;
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
-    let encoded = match format {
-        Base64Format::Standard => STANDARD.encode(&buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(&buf),
+/// Streams `reader` base64 encoded into `writer`, followed by a newline. Returns the number
+/// of bytes encoded.
+pub fn process_encode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+) -> Result<u64> {
+    let n = {
+        let mut encoder = EncoderWriter::new(&mut *writer, engine(format));
+        let n = io::copy(reader, &mut encoder)?;
+        encoder.finish()?;
+        n
     };
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(n)
+}
 
-    Ok(encoded)
+/// Streams base64 from `reader` decoded into `writer` as raw bytes, ignoring whitespace such
+/// as line wrapping. Returns the number of bytes decoded.
+pub fn process_decode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+) -> Result<u64> {
+    let mut decoder = DecoderReader::new(SkipWhitespace(reader), engine(format));
+    let n = io::copy(&mut decoder, writer)?;
+    writer.flush()?;
+    Ok(n)
 }
 
-pub fn process_decode(reader: &mut dyn Read, format: Base64Format) -> Result<String> {
-    // let mut reader = get_reader(input)?;
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
-    let buf = buf.trim();
+fn engine(format: Base64Format) -> &'static GeneralPurpose {
+    match format {
+        Base64Format::Standard => &STANDARD,
+        Base64Format::UrlSafe => &URL_SAFE_NO_PAD,
+    }
+}
 
-    let decoded = match format {
-        Base64Format::Standard => STANDARD.decode(buf)?,
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf)?,
-    };
+/// Drops ASCII whitespace from the bytes read.
+struct SkipWhitespace<R>(R);
 
-    let decoded = String::from_utf8(decoded)?;
-    // println!("{}", decoded);
-    Ok(decoded)
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.0.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut kept = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[kept] = buf[i];
+                    kept += 1;
+                }
+            }
+            // only whitespace so far: read on rather than signal the end
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -45,7 +80,9 @@ mod tests {
         let input = "Cargo.toml";
         let mut reader = crate::get_reader(input)?;
         let format = Base64Format::Standard;
-        assert!(process_encode(&mut reader, format).is_ok());
+        let mut out = Vec::new();
+        assert!(process_encode(&mut reader, &mut out, format).is_ok());
+        assert!(out.ends_with(b"\n"));
         Ok(())
     }
 
@@ -54,7 +91,38 @@ mod tests {
         let input = "fixtures/b64.txt";
         let mut reader = crate::get_reader(input)?;
         let format = Base64Format::Standard;
-        assert!(process_decode(&mut reader, format).is_ok());
+        let mut out = Vec::new();
+        assert!(process_decode(&mut reader, &mut out, format).is_ok());
+        assert!(out.starts_with(b"[package]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_round_trip() -> Result<()> {
+        // every byte value, across many buffer boundaries
+        let data = (0..100_000u32)
+            .map(|i| (i * 7 % 256) as u8)
+            .collect::<Vec<_>>();
+        for format in [Base64Format::Standard, Base64Format::UrlSafe] {
+            let mut encoded = Vec::new();
+            assert_eq!(
+                process_encode(&mut &data[..], &mut encoded, format)?,
+                100_000
+            );
+
+            // wrapped at 76 columns with CRLF, like MIME
+            let wrapped = encoded
+                .trim_ascii_end()
+                .chunks(76)
+                .collect::<Vec<_>>()
+                .join(&b"\r\n"[..]);
+            let mut decoded = Vec::new();
+            process_decode(&mut &wrapped[..], &mut decoded, format)?;
+            assert_eq!(decoded, data);
+        }
+
+        let mut out = Vec::new();
+        assert!(process_decode(&mut &b"aGVsbG8*"[..], &mut out, Base64Format::Standard).is_err());
         Ok(())
     }
 }
@@ -0,0 +1,338 @@
+use anyhow::{anyhow, Context, Result};
+use sha1::{Digest, Sha1};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The index file inside a breach db directory.
+const INDEX_FILE: &str = "sha1.idx";
+const MAGIC: &[u8; 8] = b"RCLIHIBP";
+/// The magic, then the index of the first record for each leading hash byte and the total.
+const HEADER_LEN: u64 = 8 + 257 * 8;
+/// A SHA-1 hash and its breach count, big-endian.
+const RECORD_LEN: usize = 24;
+/// Records sorted in memory per run while building, about 100 MB.
+const RUN_RECORDS: usize = 1 << 22;
+const MAX_ATTEMPTS: usize = 1000;
+
+type Record = ([u8; 20], u32);
+
+/// A sorted on-disk index of breached password SHA-1 hashes, searched without loading it.
+#[derive(Debug)]
+pub struct BreachDb {
+    file: File,
+    fanout: Vec<u64>,
+}
+
+impl BreachDb {
+    /// Opens the index in the directory `path`, or the index file `path` itself.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = index_path(path.as_ref());
+        let mut file = File::open(&path)
+            .with_context(|| format!("Cannot open breach db {}", path.display()))?;
+        let mut header = vec![0; HEADER_LEN as usize];
+        file.read_exact(&mut header)
+            .ok()
+            .filter(|_| header.starts_with(MAGIC))
+            .ok_or_else(|| anyhow!("{} is not a breach db index", path.display()))?;
+        let fanout = header[8..]
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().expect("8 byte chunks")))
+            .collect::<Vec<_>>();
+        let expected = HEADER_LEN + fanout[256] * RECORD_LEN as u64;
+        if file.metadata()?.len() != expected {
+            return Err(anyhow!("Breach db {} is truncated", path.display()));
+        }
+        Ok(Self { file, fanout })
+    }
+
+    /// How often the password appears in the corpus, 0 if never.
+    pub fn count(&self, password: &str) -> Result<u32> {
+        let hash: [u8; 20] = Sha1::digest(password.as_bytes()).into();
+        let first = hash[0] as usize;
+        let (mut lo, mut hi) = (self.fanout[first], self.fanout[first + 1]);
+        let mut record = [0; RECORD_LEN];
+        let mut file = &self.file;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            file.seek(SeekFrom::Start(HEADER_LEN + mid * RECORD_LEN as u64))?;
+            file.read_exact(&mut record)?;
+            match record[..20].cmp(&hash) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => {
+                    return Ok(u32::from_be_bytes(record[20..].try_into()?))
+                }
+            }
+        }
+        Ok(0)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.fanout[256]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Calls `generate` until it returns a password that is not in `db`.
+pub fn generate_unbreached<T: AsRef<str>>(
+    db: Option<&BreachDb>,
+    mut generate: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let Some(db) = db else {
+        return generate();
+    };
+    for _ in 0..MAX_ATTEMPTS {
+        let candidate = generate()?;
+        if db.count(candidate.as_ref())? == 0 {
+            return Ok(candidate);
+        }
+    }
+    Err(anyhow!(
+        "Every password generated in {} attempts is in the breach db",
+        MAX_ATTEMPTS
+    ))
+}
+
+/// Builds the index in the directory `output` from HIBP-style text dumps.
+///
+/// Each line is `HASH:COUNT` with a hex SHA-1 hash; the count is optional. Files named after
+/// a 5 character hash prefix, as downloaded from the range API, may hold just the 35
+/// character suffixes. Directories are read file by file. Returns the number of hashes.
+pub fn process_breach_build(inputs: &[String], output: &str) -> Result<u64> {
+    let output = Path::new(output);
+    fs::create_dir_all(output)?;
+    let mut runs = Vec::new();
+    let mut records = Vec::with_capacity(RUN_RECORDS);
+    let ret = (|| {
+        for file in input_files(inputs)? {
+            read_dump(&file, &mut |record| {
+                records.push(record);
+                if records.len() == RUN_RECORDS {
+                    runs.push(write_run(output, runs.len(), &mut records)?);
+                }
+                Ok(())
+            })?;
+        }
+        if !records.is_empty() || runs.is_empty() {
+            runs.push(write_run(output, runs.len(), &mut records)?);
+        }
+        merge_runs(&runs, &index_path(output))
+    })();
+    for run in &runs {
+        let _ = fs::remove_file(run);
+    }
+    ret
+}
+
+fn index_path(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(INDEX_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn input_files(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            entries.retain(|p| p.is_file());
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
+fn read_dump(path: &Path, emit: &mut dyn FnMut(Record) -> Result<()>) -> Result<()> {
+    let prefix = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|s| s.len() == 5 && s.bytes().all(|b| b.is_ascii_hexdigit()))
+        .unwrap_or("");
+    let reader = BufReader::new(
+        File::open(path).with_context(|| format!("Cannot open {}", path.display()))?,
+    );
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parse = || -> Option<Record> {
+            let (hash, count) = match line.split_once(':') {
+                Some((hash, count)) => (hash, count.trim().parse().ok()?),
+                None => (line, 1),
+            };
+            let hash = match hash.len() {
+                35 if !prefix.is_empty() => format!("{}{}", prefix, hash),
+                _ => hash.to_string(),
+            };
+            let mut bytes = [0; 20];
+            hex::decode_to_slice(hash, &mut bytes).ok()?;
+            Some((bytes, count))
+        };
+        let record = parse().ok_or_else(|| {
+            anyhow!(
+                "{}:{}: expected a SHA-1 hash and an optional :count",
+                path.display(),
+                i + 1
+            )
+        })?;
+        emit(record)?;
+    }
+    Ok(())
+}
+
+fn write_run(dir: &Path, n: usize, records: &mut Vec<Record>) -> Result<PathBuf> {
+    records.sort_unstable();
+    let path = dir.join(format!(".{}.run{}", INDEX_FILE, n));
+    let mut out = BufWriter::new(File::create(&path)?);
+    for (hash, count) in records.drain(..) {
+        out.write_all(&hash)?;
+        out.write_all(&count.to_be_bytes())?;
+    }
+    out.flush()?;
+    Ok(path)
+}
+
+fn read_record(reader: &mut impl Read) -> Result<Option<Record>> {
+    let mut buf = [0; RECORD_LEN];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => Ok(Some((
+            buf[..20].try_into()?,
+            u32::from_be_bytes(buf[20..].try_into()?),
+        ))),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Merges the sorted runs into the index, keeping the highest count of duplicate hashes.
+fn merge_runs(runs: &[PathBuf], index: &Path) -> Result<u64> {
+    let mut readers = runs
+        .iter()
+        .map(|run| Ok(BufReader::new(File::open(run)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = read_record(reader)? {
+            heap.push(Reverse((record, i)));
+        }
+    }
+
+    let tmp = index.with_extension("idx.tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&[0; HEADER_LEN as usize - 8])?;
+    let mut fanout = vec![0u64; 257];
+    let mut last: Option<Record> = None;
+    let mut total = 0u64;
+    let mut write = |(hash, count): Record, out: &mut BufWriter<File>| -> Result<()> {
+        out.write_all(&hash)?;
+        out.write_all(&count.to_be_bytes())?;
+        fanout[hash[0] as usize + 1] += 1;
+        total += 1;
+        Ok(())
+    };
+    while let Some(Reverse((record, i))) = heap.pop() {
+        if let Some(next) = read_record(&mut readers[i])? {
+            heap.push(Reverse((next, i)));
+        }
+        match &mut last {
+            Some(prev) if prev.0 == record.0 => prev.1 = prev.1.max(record.1),
+            _ => {
+                if let Some(prev) = last.replace(record) {
+                    write(prev, &mut out)?;
+                }
+            }
+        }
+    }
+    if let Some(prev) = last {
+        write(prev, &mut out)?;
+    }
+    for i in 1..257 {
+        fanout[i] += fanout[i - 1];
+    }
+
+    let mut file = out.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(8))?;
+    let header = fanout
+        .iter()
+        .flat_map(|n| n.to_le_bytes())
+        .collect::<Vec<_>>();
+    file.write_all(&header)?;
+    file.sync_all()?;
+    fs::rename(&tmp, index)?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha1_hex(password: &str) -> String {
+        hex::encode_upper(Sha1::digest(password.as_bytes()))
+    }
+
+    #[test]
+    fn test_breach_db() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dump = dir.path().join("pwned.txt");
+        let mut lines = (0..2000)
+            .map(|i| format!("{}:{}", sha1_hex(&format!("pass{}", i)), i + 1))
+            .collect::<Vec<_>>();
+        lines.push(sha1_hex("password").to_lowercase());
+        std::fs::write(&dump, lines.join("\r\n"))?;
+        let hash = sha1_hex("hunter2");
+        let ranges = dir.path().join("ranges");
+        std::fs::create_dir(&ranges)?;
+        std::fs::write(
+            ranges.join(format!("{}.txt", &hash[..5])),
+            format!("{}:42\n", &hash[5..]),
+        )?;
+
+        let db_dir = dir.path().join("db");
+        let inputs = [
+            dump.to_str().unwrap().into(),
+            ranges.to_str().unwrap().into(),
+        ];
+        assert_eq!(
+            process_breach_build(&inputs, db_dir.to_str().unwrap())?,
+            2002
+        );
+
+        let db = BreachDb::open(&db_dir)?;
+        assert_eq!(db.len(), 2002);
+        assert_eq!(db.count("pass0")?, 1);
+        assert_eq!(db.count("pass1999")?, 2000);
+        assert_eq!(db.count("password")?, 1);
+        assert_eq!(db.count("hunter2")?, 42);
+        assert_eq!(db.count("correct horse battery staple")?, 0);
+        assert!(std::fs::read_dir(&db_dir)?.count() == 1);
+
+        let mut candidates = ["pass7", "password", "fresh"].into_iter();
+        let pass = generate_unbreached(Some(&db), || Ok(candidates.next().unwrap()))?;
+        assert_eq!(pass, "fresh");
+        assert!(generate_unbreached(Some(&db), || Ok("pass1")).is_err());
+
+        std::fs::write(&dump, "not a hash\n")?;
+        let err = process_breach_build(&inputs[..1], db_dir.to_str().unwrap()).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("pwned.txt:1: expected a SHA-1 hash and an optional :count"));
+        Ok(())
+    }
+}
@@ -1,47 +1,871 @@
-use crate::cli::OutputFormat;
+use super::csv_encoding::DecodeReader;
+use super::csv_filter::RowFilter;
+use super::csv_nest::Nester;
+use super::csv_types::CellTyper;
+use crate::cli::{
+    ArrayMode, CsvFilterOpts, CsvReaderOpts, CsvShapeOpts, CsvTypeOpts, HeaderlessMode,
+    OutputFormat,
+};
+use crate::get_reader;
 use anyhow::Result;
-use csv::Reader;
-use serde::{Deserialize, Serialize};
+use csv::{Reader, ReaderBuilder, StringRecord, WriterBuilder};
 use serde_json::Value;
-use std::fs;
-
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct Player {
-    name: String,
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
-}
-
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-
-    // for result in reader.deserialize() {
-    //     let record: Player = result?;
-    //     ret.push(record);
-    // }
-
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        // println!("{:?}", json_value);
-        ret.push(json_value);
-    }
-
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// Converts a CSV file record by record, so memory usage does not grow with the input size.
+///
+/// Rows are filtered before they are typed. Only sorting needs to hold the matching rows
+/// in memory.
+pub fn process_csv(
+    input: &str,
+    output: String,
+    format: OutputFormat,
+    opts: &CsvReaderOpts,
+    shape: &CsvShapeOpts,
+    types: &CsvTypeOpts,
+    filter: &CsvFilterOpts,
+) -> Result<()> {
+    let (mut reader, headers) = open_csv(input, opts)?;
+    let typer = CellTyper::new(&headers, types)?;
+    let filter = RowFilter::new(&headers, filter)?;
+    let nester = if shape.nest {
+        if matches!(format, OutputFormat::Markdown | OutputFormat::Html) {
+            anyhow::bail!("--nest is not supported for {} output", format);
+        }
+        Some(Nester::new(filter.headers())?)
+    } else {
+        None
+    };
+    let file = BufWriter::new(File::create(output)?);
+    let mut writer = record_writer(format, file, filter.headers());
+
+    let as_array = !opts.header && shape.headerless == HeaderlessMode::Array;
+    let mut write = |cells: Vec<Value>| {
+        let cells = filter.project(cells);
+        let json_value = if as_array {
+            Value::Array(cells)
+        } else if let Some(nester) = &nester {
+            nester.nest(cells)
+        } else {
+            filter.headers().iter().zip(cells).collect::<Value>()
+        };
+        writer.write_record(&json_value)
+    };
+
+    let mut sorted = Vec::new();
+    let mut record = StringRecord::new();
+    let mut row = 0;
+    while reader.read_record(&mut record)? {
+        row += 1;
+        let cells = typer.convert(row, &record)?;
+        if !filter.matches(&cells) {
+            continue;
+        }
+        if filter.sorts() {
+            sorted.push(cells);
+        } else {
+            write(cells)?;
+        }
+    }
+    // stable, so rows with equal keys keep their input order
+    sorted.sort_by(|a, b| filter.compare(a, b));
+    for cells in sorted {
+        write(cells)?;
+    }
+    writer.finish()
+}
+
+/// Opens a CSV input ("-" for stdin) and returns the reader with its column names.
+///
+/// Files without a header row get generated names: `col_0`, `col_1`, ...
+pub fn open_csv(
+    input: &str,
+    opts: &CsvReaderOpts,
+) -> Result<(Reader<Box<dyn Read>>, StringRecord)> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(opts.delimiter)
+        .has_headers(opts.header)
+        .from_reader(
+            Box::new(DecodeReader::new(get_reader(input)?, opts.encoding)) as Box<dyn Read>,
+        );
+
+    let headers = if opts.header {
+        reader.headers()?.clone()
+    } else {
+        // without headers, `headers()` peeks at the first record without consuming it
+        let len = reader.headers()?.len();
+        (0..len).map(|i| format!("col_{}", i)).collect()
     };
-    fs::write(output, content)?;
+    Ok((reader, headers))
+}
 
-    // let json = serde_json::to_string_pretty(&ret)?;
-    // fs::write(output, json)?;
+/// Converts JSON, YAML or NDJSON records into CSV.
+///
+/// Nested objects are flattened into dotted column names, and the headers are the union of
+/// all keys in first-seen order unless `columns` picks and orders them explicitly.
+pub fn process_csv_import(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    columns: &[String],
+    arrays: ArrayMode,
+    array_separator: &str,
+    delimiter: u8,
+) -> Result<()> {
+    let records = read_records(input, format)?;
+    let flattener = Flattener {
+        arrays,
+        array_separator,
+    };
+    let rows = records
+        .iter()
+        .flat_map(|record| flattener.flatten(record, ""))
+        .collect::<Vec<_>>();
+
+    let headers = if columns.is_empty() {
+        let mut seen = HashSet::new();
+        rows.iter()
+            .flatten()
+            .filter(|(k, _)| seen.insert(k.as_str()))
+            .map(|(k, _)| k.clone())
+            .collect()
+    } else {
+        columns.to_vec()
+    };
 
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(output)?;
+    writer.write_record(&headers)?;
+    for row in rows {
+        let record = headers.iter().map(|h| {
+            row.iter()
+                .find(|(k, _)| k == h)
+                .map_or("", |(_, v)| v.as_str())
+        });
+        writer.write_record(record)?;
+    }
+    writer.flush()?;
     Ok(())
 }
+
+fn read_records(input: &str, format: OutputFormat) -> Result<Vec<Value>> {
+    let reader = get_reader(input)?;
+    let value: Value = match format {
+        OutputFormat::Json => serde_json::from_reader(reader)?,
+        OutputFormat::Yaml => serde_yaml::from_reader(reader)?,
+        OutputFormat::Ndjson => {
+            let mut records = Vec::new();
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(serde_json::from_str(&line)?);
+                }
+            }
+            Value::Array(records)
+        }
+        f => anyhow::bail!("Cannot read {} input, use json, yaml or ndjson", f),
+    };
+    Ok(match value {
+        Value::Array(records) => records,
+        v => vec![v],
+    })
+}
+
+/// A flattened record: dotted column names and their cell text, in document order.
+type FlatRow = Vec<(String, String)>;
+
+struct Flattener<'a> {
+    arrays: ArrayMode,
+    array_separator: &'a str,
+}
+
+impl Flattener<'_> {
+    /// Flattens a value under `prefix`; exploded arrays can turn one value into many rows.
+    fn flatten(&self, value: &Value, prefix: &str) -> Vec<FlatRow> {
+        match value {
+            Value::Object(map) => {
+                let mut rows = vec![FlatRow::new()];
+                for (k, v) in map {
+                    let key = if prefix.is_empty() {
+                        k.clone()
+                    } else {
+                        format!("{}.{}", prefix, k)
+                    };
+                    let sub = self.flatten(v, &key);
+                    rows = rows
+                        .iter()
+                        .flat_map(|row| {
+                            sub.iter().map(move |s| {
+                                let mut row = row.clone();
+                                row.extend(s.iter().cloned());
+                                row
+                            })
+                        })
+                        .collect();
+                }
+                rows
+            }
+            Value::Array(items) if self.arrays == ArrayMode::Explode && !items.is_empty() => items
+                .iter()
+                .flat_map(|item| self.flatten(item, prefix))
+                .collect(),
+            Value::Array(items) => {
+                let text = items
+                    .iter()
+                    .map(|item| match item {
+                        Value::Object(_) | Value::Array(_) => item.to_string(),
+                        v => cell_text(v),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(self.array_separator);
+                vec![vec![(self.key(prefix), text)]]
+            }
+            v => vec![vec![(self.key(prefix), cell_text(v))]],
+        }
+    }
+
+    fn key(&self, prefix: &str) -> String {
+        if prefix.is_empty() {
+            "value".to_string()
+        } else {
+            prefix.to_string()
+        }
+    }
+}
+
+/// Incrementally serializes records into a single output document.
+pub trait RecordWriter {
+    fn write_record(&mut self, record: &Value) -> Result<()>;
+    fn finish(&mut self) -> Result<()>;
+}
+
+pub fn record_writer<'a, W: Write + 'a>(
+    format: OutputFormat,
+    out: W,
+    headers: &StringRecord,
+) -> Box<dyn RecordWriter + 'a> {
+    let headers = headers.iter().map(String::from).collect();
+    match format {
+        OutputFormat::Json => Box::new(JsonWriter { out, count: 0 }),
+        OutputFormat::Yaml => Box::new(YamlWriter { out, count: 0 }),
+        OutputFormat::Toml => Box::new(TomlWriter { out }),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { out }),
+        OutputFormat::Markdown => Box::new(TableWriter::<_, Markdown>::new(out, headers)),
+        OutputFormat::Html => Box::new(TableWriter::<_, Html>::new(out, headers)),
+    }
+}
+
+/// Writes a pretty-printed JSON array, one element at a time.
+struct JsonWriter<W> {
+    out: W,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter for JsonWriter<W> {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.out.write_all(sep.as_bytes())?;
+        let content = serde_json::to_string_pretty(record)?;
+        for (i, line) in content.lines().enumerate() {
+            if i > 0 {
+                self.out.write_all(b"\n")?;
+            }
+            write!(self.out, "  {}", line)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let end = if self.count == 0 { "[]" } else { "\n]" };
+        self.out.write_all(end.as_bytes())?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes a YAML sequence, one item at a time.
+struct YamlWriter<W> {
+    out: W,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter for YamlWriter<W> {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let content = serde_yaml::to_string(&[record])?;
+        self.out.write_all(content.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.out.write_all(b"[]\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes a TOML array of tables named `rows`. Null cells are omitted since TOML has no null.
+struct TomlWriter<W> {
+    out: W,
+}
+
+impl<W: Write> RecordWriter for TomlWriter<W> {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        let Value::Object(map) = record else {
+            anyhow::bail!("TOML output requires named columns, use `--headerless named`");
+        };
+        let doc = serde_json::json!({ "rows": [without_nulls(map)] });
+        self.out.write_all(toml::to_string(&doc)?.as_bytes())?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes newline delimited JSON, one compact object per line.
+struct NdjsonWriter<W> {
+    out: W,
+}
+
+impl<W: Write> RecordWriter for NdjsonWriter<W> {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.out, record)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Markup of a table format, written row by row by [`TableWriter`].
+trait TableStyle {
+    fn header(out: &mut dyn Write, headers: &[String]) -> Result<()>;
+    fn row(out: &mut dyn Write, cells: &[String]) -> Result<()>;
+    fn footer(out: &mut dyn Write) -> Result<()>;
+}
+
+struct TableWriter<W, S> {
+    out: W,
+    headers: Vec<String>,
+    started: bool,
+    _style: std::marker::PhantomData<S>,
+}
+
+impl<W: Write, S: TableStyle> TableWriter<W, S> {
+    fn new(out: W, headers: Vec<String>) -> Self {
+        Self {
+            out,
+            headers,
+            started: false,
+            _style: std::marker::PhantomData,
+        }
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if !self.started {
+            S::header(&mut self.out, &self.headers)?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, S: TableStyle> RecordWriter for TableWriter<W, S> {
+    fn write_record(&mut self, record: &Value) -> Result<()> {
+        self.start()?;
+        let cells = match record {
+            Value::Object(map) => self
+                .headers
+                .iter()
+                .map(|h| map.get(h).map(cell_text).unwrap_or_default())
+                .collect::<Vec<_>>(),
+            Value::Array(values) => values.iter().map(cell_text).collect(),
+            v => vec![cell_text(v)],
+        };
+        S::row(&mut self.out, &cells)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.start()?;
+        S::footer(&mut self.out)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// TOML has no null, so empty cells are left out, including those of nested tables and arrays.
+fn without_nulls(map: &serde_json::Map<String, Value>) -> serde_json::Map<String, Value> {
+    map.iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, v)| (k.clone(), value_without_nulls(v)))
+        .collect()
+}
+
+fn value_without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(inner) => Value::Object(without_nulls(inner)),
+        Value::Array(items) => items
+            .iter()
+            .filter(|v| !v.is_null())
+            .map(value_without_nulls)
+            .collect(),
+        v => v.clone(),
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// GitHub flavored markdown table.
+struct Markdown;
+
+impl Markdown {
+    fn line(out: &mut dyn Write, cells: impl Iterator<Item = String>) -> Result<()> {
+        let cells = cells.collect::<Vec<_>>();
+        writeln!(out, "| {} |", cells.join(" | "))?;
+        Ok(())
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('|', "\\|").replace('\n', "<br>")
+    }
+}
+
+impl TableStyle for Markdown {
+    fn header(out: &mut dyn Write, headers: &[String]) -> Result<()> {
+        Self::line(out, headers.iter().map(|h| Self::escape(h)))?;
+        Self::line(out, headers.iter().map(|_| "---".to_string()))
+    }
+
+    fn row(out: &mut dyn Write, cells: &[String]) -> Result<()> {
+        Self::line(out, cells.iter().map(|c| Self::escape(c)))
+    }
+
+    fn footer(_out: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Standalone HTML `<table>` element.
+struct Html;
+
+impl Html {
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&#39;")
+    }
+}
+
+impl TableStyle for Html {
+    fn header(out: &mut dyn Write, headers: &[String]) -> Result<()> {
+        writeln!(out, "<table>\n  <thead>\n    <tr>")?;
+        for h in headers {
+            writeln!(out, "      <th>{}</th>", Self::escape(h))?;
+        }
+        writeln!(out, "    </tr>\n  </thead>\n  <tbody>")?;
+        Ok(())
+    }
+
+    fn row(out: &mut dyn Write, cells: &[String]) -> Result<()> {
+        writeln!(out, "    <tr>")?;
+        for c in cells {
+            writeln!(out, "      <td>{}</td>", Self::escape(c))?;
+        }
+        writeln!(out, "    </tr>")?;
+        Ok(())
+    }
+
+    fn footer(out: &mut dyn Write) -> Result<()> {
+        writeln!(out, "  </tbody>\n</table>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    fn reader_opts(delimiter: u8, header: bool) -> CsvReaderOpts {
+        CsvReaderOpts {
+            delimiter,
+            header,
+            encoding: None,
+        }
+    }
+
+    fn untyped() -> CsvTypeOpts {
+        CsvTypeOpts {
+            infer_types: false,
+            infer_dates: false,
+            schema: None,
+        }
+    }
+
+    fn unfiltered() -> CsvFilterOpts {
+        CsvFilterOpts {
+            select: vec![],
+            filter: vec![],
+            sort_by: vec![],
+        }
+    }
+
+    fn shape(headerless: HeaderlessMode) -> CsvShapeOpts {
+        CsvShapeOpts {
+            headerless,
+            nest: false,
+        }
+    }
+
+    fn convert(content: &str, opts: &CsvReaderOpts, headerless: HeaderlessMode) -> Result<Value> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input.csv");
+        let output = dir.path().join("output.json");
+        fs::write(&input, content)?;
+        process_csv(
+            input.to_str().unwrap(),
+            output.to_string_lossy().into_owned(),
+            OutputFormat::Json,
+            opts,
+            &shape(headerless),
+            &untyped(),
+            &unfiltered(),
+        )?;
+        Ok(serde_json::from_str(&fs::read_to_string(output)?)?)
+    }
+
+    fn write_all(format: OutputFormat, records: &[Value]) -> Result<String> {
+        let headers = StringRecord::from(vec!["Name", "Kit Number"]);
+        let mut buf = Vec::new();
+        {
+            let mut writer = record_writer(format, &mut buf, &headers);
+            for record in records {
+                writer.write_record(record)?;
+            }
+            writer.finish()?;
+        }
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_writers_match_buffered_output() -> Result<()> {
+        let records = vec![
+            json!({"Name": "Paulo Dybala", "Kit Number": "10"}),
+            json!({"Name": "Gianluigi Buffon", "Kit Number": "77"}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Json, &records)?,
+            serde_json::to_string_pretty(&records)?
+        );
+        assert_eq!(
+            write_all(OutputFormat::Yaml, &records)?,
+            serde_yaml::to_string(&records)?
+        );
+        assert_eq!(write_all(OutputFormat::Json, &[])?, "[]");
+        assert_eq!(write_all(OutputFormat::Yaml, &[])?, "[]\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_table_and_line_writers() -> Result<()> {
+        let records = vec![
+            json!({"Name": "A|B <C>", "Kit Number": 10}),
+            json!({"Name": "Buffon", "Kit Number": null}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Ndjson, &records)?,
+            "{\"Name\":\"A|B <C>\",\"Kit Number\":10}\n{\"Name\":\"Buffon\",\"Kit Number\":null}\n"
+        );
+        assert_eq!(
+            write_all(OutputFormat::Markdown, &records)?,
+            "| Name | Kit Number |\n| --- | --- |\n| A\\|B <C> | 10 |\n| Buffon |  |\n"
+        );
+        let html = write_all(OutputFormat::Html, &records)?;
+        assert!(html.starts_with("<table>\n  <thead>"));
+        assert!(html.contains("<td>A|B &lt;C&gt;</td>"));
+        assert!(html.ends_with("</tbody>\n</table>\n"));
+
+        let toml = write_all(OutputFormat::Toml, &records)?;
+        let doc: toml::Value = toml::from_str(&toml)?;
+        let rows = doc["rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["Kit Number"].as_integer(), Some(10));
+        assert!(rows[1].get("Kit Number").is_none());
+        assert!(write_all(OutputFormat::Toml, &[json!(["a", "b"])]).is_err());
+        Ok(())
+    }
+
+    fn import(
+        content: &str,
+        format: OutputFormat,
+        columns: &[&str],
+        arrays: ArrayMode,
+    ) -> Result<String> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input");
+        let output = dir.path().join("output.csv");
+        fs::write(&input, content)?;
+        let columns = columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        process_csv_import(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            format,
+            &columns,
+            arrays,
+            ";",
+            b',',
+        )?;
+        Ok(fs::read_to_string(output)?)
+    }
+
+    #[test]
+    fn test_process_csv_import() -> Result<()> {
+        let content = r#"[
+            {"name": "Buffon", "address": {"city": "Turin", "zip": "10121"}, "tags": ["gk", "captain"]},
+            {"name": "Dybala", "kit": 10, "active": true, "tags": []}
+        ]"#;
+        assert_eq!(
+            import(content, OutputFormat::Json, &[], ArrayMode::Join)?,
+            "name,address.city,address.zip,tags,kit,active\n\
+             Buffon,Turin,10121,gk;captain,,\n\
+             Dybala,,,,10,true\n"
+        );
+        assert_eq!(
+            import(
+                content,
+                OutputFormat::Json,
+                &["tags", "name"],
+                ArrayMode::Explode
+            )?,
+            "tags,name\ngk,Buffon\ncaptain,Buffon\n,Dybala\n"
+        );
+
+        let content = "{\"a\": 1}\n\n{\"b\": {\"c\": null}}\n";
+        assert_eq!(
+            import(content, OutputFormat::Ndjson, &[], ArrayMode::Join)?,
+            "a,b.c\n1,\n,\n"
+        );
+        let content = "- name: Buffon\n  kit: 77\n";
+        assert_eq!(
+            import(content, OutputFormat::Yaml, &[], ArrayMode::Join)?,
+            "name,kit\nBuffon,77\n"
+        );
+        assert!(import(content, OutputFormat::Toml, &[], ArrayMode::Join).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_filtered() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("output.ndjson");
+        let filter = CsvFilterOpts {
+            select: vec!["Name".into(), "Kit Number".into()],
+            filter: vec!["Position=Goalkeeper".parse()?, "Kit Number>1".parse()?],
+            sort_by: vec!["Kit Number:desc".parse()?],
+        };
+        process_csv(
+            "assets/juventus.csv",
+            output.to_string_lossy().into_owned(),
+            OutputFormat::Ndjson,
+            &reader_opts(b',', true),
+            &shape(HeaderlessMode::Named),
+            &untyped(),
+            &filter,
+        )?;
+        assert_eq!(
+            fs::read_to_string(&output)?,
+            "{\"Name\":\"Gianluigi Buffon\",\"Kit Number\":\"77\"}\n\
+             {\"Name\":\"Mattia Perin\",\"Kit Number\":\"37\"}\n\
+             {\"Name\":\"Carlo Pinsoglio\",\"Kit Number\":\"31\"}\n"
+        );
+
+        // conditions see the cells as typed by the schema
+        let input = dir.path().join("input.csv");
+        let schema = dir.path().join("schema.yaml");
+        fs::write(
+            &input,
+            "name,score,joined\nA,9.0e0,2024-1-5\nB,8.5,2024-02-01\n",
+        )?;
+        fs::write(&schema, "score: float\njoined: date\n")?;
+        let types = CsvTypeOpts {
+            schema: Some(schema.to_string_lossy().into_owned()),
+            ..untyped()
+        };
+        let filter = CsvFilterOpts {
+            select: vec!["name".into()],
+            filter: vec!["score>=9".parse()?, "joined<2024-01-10".parse()?],
+            sort_by: vec![],
+        };
+        process_csv(
+            input.to_str().unwrap(),
+            output.to_string_lossy().into_owned(),
+            OutputFormat::Ndjson,
+            &reader_opts(b',', true),
+            &shape(HeaderlessMode::Named),
+            &types,
+            &filter,
+        )?;
+        assert_eq!(fs::read_to_string(output)?, "{\"name\":\"A\"}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_delimiters() -> Result<()> {
+        let expected = json!([{"name": "Buffon", "kit": "77"}]);
+        for delimiter in [b'\t', b';', b'|'] {
+            let d = delimiter as char;
+            let content = format!("name{d}kit\nBuffon{d}77\n");
+            let ret = convert(
+                &content,
+                &reader_opts(delimiter, true),
+                HeaderlessMode::Named,
+            )?;
+            assert_eq!(ret, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_headerless() -> Result<()> {
+        let content = "Buffon;77\nDybala;10\n";
+        let opts = reader_opts(b';', false);
+        assert_eq!(
+            convert(content, &opts, HeaderlessMode::Named)?,
+            json!([
+                {"col_0": "Buffon", "col_1": "77"},
+                {"col_0": "Dybala", "col_1": "10"}
+            ])
+        );
+        assert_eq!(
+            convert(content, &opts, HeaderlessMode::Array)?,
+            json!([["Buffon", "77"], ["Dybala", "10"]])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_nested() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("input.csv");
+        let output = dir.path().join("output.json");
+        fs::write(
+            &input,
+            "name,address.city,address.zip,tags[0],tags[1]\nBuffon,Turin,,captain,keeper\n",
+        )?;
+        let shape = CsvShapeOpts {
+            headerless: HeaderlessMode::Named,
+            nest: true,
+        };
+        let run = |format| {
+            process_csv(
+                input.to_str().unwrap(),
+                output.to_string_lossy().into_owned(),
+                format,
+                &reader_opts(b',', true),
+                &shape,
+                &untyped(),
+                &unfiltered(),
+            )
+        };
+        run(OutputFormat::Json)?;
+        let ret: Value = serde_json::from_str(&fs::read_to_string(&output)?)?;
+        assert_eq!(
+            ret,
+            json!([{
+                "name": "Buffon",
+                "address": {"city": "Turin", "zip": ""},
+                "tags": ["captain", "keeper"]
+            }])
+        );
+        run(OutputFormat::Toml)?;
+        assert!(fs::read_to_string(&output)?.contains("[rows.address]\ncity = \"Turin\""));
+        assert!(run(OutputFormat::Markdown).is_err());
+
+        // typed empty cells are nulls, which TOML cannot hold, also inside arrays
+        fs::write(&input, "name,tags[0],tags[1],kits[0].n\nBuffon,,x,\n")?;
+        let typed = CsvTypeOpts {
+            infer_types: true,
+            ..untyped()
+        };
+        process_csv(
+            input.to_str().unwrap(),
+            output.to_string_lossy().into_owned(),
+            OutputFormat::Toml,
+            &reader_opts(b',', true),
+            &shape,
+            &typed,
+            &unfiltered(),
+        )?;
+        let ret: toml::Value = toml::from_str(&fs::read_to_string(&output)?)?;
+        assert_eq!(ret["rows"][0]["tags"].as_array().map(Vec::len), Some(1));
+        assert_eq!(
+            ret["rows"][0]["kits"][0].as_table().map(|t| t.len()),
+            Some(0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_large_input() -> Result<()> {
+        const ROWS: usize = 100_000;
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("large.csv");
+        let mut file = BufWriter::new(File::create(&input)?);
+        writeln!(file, "id,name,score")?;
+        for i in 0..ROWS {
+            writeln!(file, "{},player-{},{}", i, i, i % 100)?;
+        }
+        file.flush()?;
+        drop(file);
+
+        for format in [OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Ndjson] {
+            let output = dir.path().join(format!("large.{}", format));
+            process_csv(
+                input.to_str().unwrap(),
+                output.to_string_lossy().into_owned(),
+                format,
+                &reader_opts(b',', true),
+                &shape(HeaderlessMode::Named),
+                &untyped(),
+                &unfiltered(),
+            )?;
+            let content = fs::read_to_string(&output)?;
+            let ret: Vec<Value> = match format {
+                OutputFormat::Json => serde_json::from_str(&content)?,
+                OutputFormat::Yaml => serde_yaml::from_str(&content)?,
+                _ => content
+                    .lines()
+                    .map(serde_json::from_str)
+                    .collect::<Result<_, _>>()?,
+            };
+            assert_eq!(ret.len(), ROWS);
+            assert_eq!(
+                ret[ROWS - 1],
+                json!({"id": "99999", "name": "player-99999", "score": "99"})
+            );
+        }
+        Ok(())
+    }
+}
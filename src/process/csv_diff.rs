@@ -0,0 +1,268 @@
+use super::csv_convert::open_csv;
+use crate::cli::{CsvReaderOpts, OutputFormat};
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+/// Differences between two versions of a CSV file, matched by key columns.
+#[derive(Debug, Default, Serialize)]
+pub struct CsvDiff {
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+    pub added: Vec<Map<String, Value>>,
+    pub removed: Vec<Map<String, Value>>,
+    pub changed: Vec<RowChange>,
+}
+
+/// A row present in both files whose non-key cells differ.
+#[derive(Debug, Serialize)]
+pub struct RowChange {
+    pub key: Map<String, Value>,
+    pub changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl CsvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.columns_added.is_empty()
+            && self.columns_removed.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// Compares two CSV files row by row, matching rows on the `key` columns.
+///
+/// The old file is held in memory, the new one is streamed. Cells are only compared for
+/// columns present in both files; added or removed columns are reported separately.
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    key: &[String],
+    opts: &CsvReaderOpts,
+) -> Result<CsvDiff> {
+    let (mut old_reader, old_headers) = open_csv(old, opts)?;
+    let (mut new_reader, new_headers) = open_csv(new, opts)?;
+    let old_key = key_columns(&old_headers, key, old)?;
+    let new_key = key_columns(&new_headers, key, new)?;
+
+    let mut old_rows = Vec::new();
+    let mut index = HashMap::new();
+    for (row, result) in old_reader.records().enumerate() {
+        let record = result?;
+        let k = key_of(&record, &old_key);
+        if index.insert(k.clone(), old_rows.len()).is_some() {
+            return Err(anyhow!(
+                "Duplicate key {:?} in {} at row {}",
+                k,
+                old,
+                row + 1
+            ));
+        }
+        old_rows.push(Some(record));
+    }
+
+    let mut diff = CsvDiff {
+        columns_added: missing_from(&new_headers, &old_headers),
+        columns_removed: missing_from(&old_headers, &new_headers),
+        ..Default::default()
+    };
+    // (name, old index, new index) of the columns both files share
+    let common = old_headers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| Some((h, i, new_headers.iter().position(|n| n == h)?)))
+        .filter(|(h, _, _)| !key.iter().any(|k| k == h))
+        .collect::<Vec<_>>();
+
+    let mut seen = HashSet::new();
+    for (row, result) in new_reader.records().enumerate() {
+        let record = result?;
+        let k = key_of(&record, &new_key);
+        if !seen.insert(k.clone()) {
+            return Err(anyhow!(
+                "Duplicate key {:?} in {} at row {}",
+                k,
+                new,
+                row + 1
+            ));
+        }
+        let Some(old_record) = index.get(&k).and_then(|&i| old_rows[i].take()) else {
+            diff.added.push(to_object(&new_headers, &record));
+            continue;
+        };
+        let changes = common
+            .iter()
+            .filter_map(|(name, i, j)| {
+                let (o, n) = (old_record.get(*i)?, record.get(*j)?);
+                (o != n).then(|| CellChange {
+                    column: name.to_string(),
+                    old: o.to_string(),
+                    new: n.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            let key = key.iter().cloned().zip(k.into_iter().map(Value::from));
+            diff.changed.push(RowChange {
+                key: key.collect(),
+                changes,
+            });
+        }
+    }
+    diff.removed = old_rows
+        .into_iter()
+        .flatten()
+        .map(|record| to_object(&old_headers, &record))
+        .collect();
+    Ok(diff)
+}
+
+/// Writes a diff as a human readable report, or as JSON/YAML for automation.
+pub fn write_diff(diff: &CsvDiff, format: Option<OutputFormat>, out: &mut dyn Write) -> Result<()> {
+    match format {
+        None => write_report(diff, out)?,
+        Some(OutputFormat::Json) => {
+            serde_json::to_writer_pretty(&mut *out, diff)?;
+            writeln!(out)?;
+        }
+        Some(OutputFormat::Yaml) => serde_yaml::to_writer(&mut *out, diff)?,
+        Some(f) => return Err(anyhow!("Cannot write a diff as {}, use json or yaml", f)),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_report(diff: &CsvDiff, out: &mut dyn Write) -> Result<()> {
+    for c in &diff.columns_added {
+        writeln!(out, "+ column {}", c)?;
+    }
+    for c in &diff.columns_removed {
+        writeln!(out, "- column {}", c)?;
+    }
+    for row in &diff.removed {
+        writeln!(out, "- {}", describe(row))?;
+    }
+    for row in &diff.added {
+        writeln!(out, "+ {}", describe(row))?;
+    }
+    for row in &diff.changed {
+        writeln!(out, "~ {}", describe(&row.key))?;
+        for c in &row.changes {
+            writeln!(out, "    {}: {:?} -> {:?}", c.column, c.old, c.new)?;
+        }
+    }
+    writeln!(
+        out,
+        "{} added, {} removed, {} changed",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    )?;
+    Ok(())
+}
+
+fn describe(row: &Map<String, Value>) -> String {
+    row.iter()
+        .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn key_columns(headers: &StringRecord, key: &[String], file: &str) -> Result<Vec<usize>> {
+    key.iter()
+        .map(|k| {
+            headers
+                .iter()
+                .position(|h| h == k)
+                .ok_or_else(|| anyhow!("Key column {:?} not found in {}", k, file))
+        })
+        .collect()
+}
+
+fn key_of(record: &StringRecord, columns: &[usize]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|&i| record.get(i).unwrap_or_default().to_string())
+        .collect()
+}
+
+fn missing_from(headers: &StringRecord, other: &StringRecord) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|h| !other.iter().any(|o| o == *h))
+        .map(String::from)
+        .collect()
+}
+
+fn to_object(headers: &StringRecord, record: &StringRecord) -> Map<String, Value> {
+    headers
+        .iter()
+        .zip(record.iter())
+        .map(|(h, v)| (h.to_string(), Value::from(v)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_process_csv_diff() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let old = dir.path().join("old.csv");
+        let new = dir.path().join("new.csv");
+        fs::write(
+            &old,
+            "Name,Kit,Team\nBuffon,1,Juve\nDybala,10,Juve\nKean,18,Juve\n",
+        )?;
+        fs::write(
+            &new,
+            "Name,Kit,Age\nBuffon,77,41\nDybala,10,25\nRonaldo,7,34\n",
+        )?;
+        let opts = CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        };
+        let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+        let diff = process_csv_diff(old, new, &["Name".into()], &opts)?;
+        assert_eq!(diff.columns_added, ["Age"]);
+        assert_eq!(diff.columns_removed, ["Team"]);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0]["Name"], "Ronaldo");
+        assert_eq!(diff.removed[0]["Name"], "Kean");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key["Name"], "Buffon");
+        assert_eq!(
+            diff.changed[0].changes,
+            vec![CellChange {
+                column: "Kit".into(),
+                old: "1".into(),
+                new: "77".into()
+            }]
+        );
+
+        let mut buf = Vec::new();
+        write_diff(&diff, None, &mut buf)?;
+        let report = String::from_utf8(buf)?;
+        assert!(report.contains("~ Name=Buffon\n    Kit: \"1\" -> \"77\"\n"));
+        assert!(report.ends_with("1 added, 1 removed, 1 changed\n"));
+
+        let diff = process_csv_diff(old, old, &["Name".into()], &opts)?;
+        assert!(diff.is_empty());
+        assert!(process_csv_diff(old, new, &["Team".into()], &opts).is_err());
+        Ok(())
+    }
+}
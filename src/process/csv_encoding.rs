@@ -0,0 +1,134 @@
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_8};
+use std::io::{self, Read};
+
+const BUF_SIZE: usize = 8 * 1024;
+
+/// Transcodes a byte stream to UTF-8 as it is read.
+///
+/// A UTF-8 or UTF-16 byte order mark wins over the declared encoding and is stripped.
+/// Invalid input fails with the byte offset of the offending sequence instead of being
+/// replaced with U+FFFD.
+pub struct DecodeReader<R> {
+    inner: R,
+    decoder: Decoder,
+    input: Vec<u8>,
+    input_start: usize,
+    input_end: usize,
+    output: Vec<u8>,
+    output_start: usize,
+    output_end: usize,
+    // bytes of `inner` consumed by the decoder so far
+    offset: u64,
+    eof: bool,
+    // the decoder has flushed its last output and must not be called again
+    finished: bool,
+}
+
+impl<R: Read> DecodeReader<R> {
+    /// Decodes `inner` as `encoding`, UTF-8 when `None`.
+    pub fn new(inner: R, encoding: Option<&'static Encoding>) -> Self {
+        Self {
+            inner,
+            decoder: encoding.unwrap_or(UTF_8).new_decoder(),
+            input: vec![0; BUF_SIZE],
+            input_start: 0,
+            input_end: 0,
+            // room for the worst case expansion of a full input buffer
+            output: vec![0; BUF_SIZE * 3 + 16],
+            output_start: 0,
+            output_end: 0,
+            offset: 0,
+            eof: false,
+            finished: false,
+        }
+    }
+
+    fn fill_output(&mut self) -> io::Result<()> {
+        self.output_start = 0;
+        self.output_end = 0;
+        while !self.finished {
+            if self.input_start == self.input_end && !self.eof {
+                self.input_start = 0;
+                self.input_end = self.inner.read(&mut self.input)?;
+                self.eof = self.input_end == 0;
+            }
+            let (result, read, written) = self.decoder.decode_to_utf8_without_replacement(
+                &self.input[self.input_start..self.input_end],
+                &mut self.output,
+                self.eof,
+            );
+            self.input_start += read;
+            self.offset += read as u64;
+            self.output_start = 0;
+            self.output_end = written;
+            if let DecoderResult::Malformed(bad, after) = result {
+                let at = self.offset - u64::from(bad) - u64::from(after);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "invalid {} byte sequence at byte offset {}",
+                        self.decoder.encoding().name(),
+                        at
+                    ),
+                ));
+            }
+            self.finished = self.eof && result == DecoderResult::InputEmpty;
+            if written > 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output_start == self.output_end {
+            self.fill_output()?;
+        }
+        let n = buf.len().min(self.output_end - self.output_start);
+        buf[..n].copy_from_slice(&self.output[self.output_start..self.output_start + n]);
+        self.output_start += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_1252;
+
+    fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> io::Result<String> {
+        let mut s = String::new();
+        DecodeReader::new(bytes, encoding).read_to_string(&mut s)?;
+        Ok(s)
+    }
+
+    #[test]
+    fn test_decode_reader() -> anyhow::Result<()> {
+        assert_eq!(decode(b"\xEF\xBB\xBFName\n", None)?, "Name\n");
+        assert_eq!(decode(b"\xFF\xFEN\x00\xE9\x00\n\x00", None)?, "Né\n");
+        assert_eq!(decode(b"\xFE\xFF\x00N\x00\xE9", Some(WINDOWS_1252))?, "Né");
+        assert_eq!(decode(b"Caf\xE9", Encoding::for_label(b"latin1"))?, "Café");
+
+        // reading on after the end keeps returning 0
+        let mut reader = DecodeReader::new(&b"Caf\xE9"[..], Some(WINDOWS_1252));
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+        assert_eq!(s, "Café");
+        let mut buf = [0; 16];
+        assert_eq!(reader.read(&mut buf)?, 0);
+        assert_eq!(reader.read(&mut buf)?, 0);
+
+        let big = "é,".repeat(BUF_SIZE);
+        assert_eq!(decode(big.as_bytes(), None)?, big);
+
+        let err = decode(b"Name\nCaf\xE9\n", None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "invalid UTF-8 byte sequence at byte offset 8"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+use crate::cli::CsvFilterOpts;
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Comparison operators, longest first so `>=` wins over `>`.
+const OPERATORS: &[(&str, Op)] = &[
+    ("!~", Op::NotMatch),
+    (">=", Op::Ge),
+    ("<=", Op::Le),
+    ("!=", Op::Ne),
+    ("==", Op::Eq),
+    ("=", Op::Eq),
+    (">", Op::Gt),
+    ("<", Op::Lt),
+    ("~", Op::Match),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Match,
+    NotMatch,
+}
+
+/// A `--where` condition such as `Kit Number>10` or `Name~^G`.
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    column: String,
+    op: Op,
+    value: String,
+    regex: Option<Regex>,
+}
+
+/// A `--sort-by` key such as `Name` or `Kit Number:desc`.
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    column: String,
+    desc: bool,
+}
+
+/// Filtering, sorting and column selection resolved against the headers of a file.
+#[derive(Debug)]
+pub struct RowFilter {
+    predicates: Vec<(usize, Predicate)>,
+    sort_keys: Vec<(usize, bool)>,
+    selection: Option<Vec<usize>>,
+    headers: StringRecord,
+}
+
+impl RowFilter {
+    pub fn new(headers: &StringRecord, opts: &CsvFilterOpts) -> Result<Self> {
+        let index = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow!("Unknown column {:?}", name))
+        };
+        let predicates = opts
+            .filter
+            .iter()
+            .map(|p| Ok((index(&p.column)?, p.clone())))
+            .collect::<Result<_>>()?;
+        let sort_keys = opts
+            .sort_by
+            .iter()
+            .map(|k| Ok((index(&k.column)?, k.desc)))
+            .collect::<Result<_>>()?;
+        let selection = if opts.select.is_empty() {
+            None
+        } else {
+            Some(
+                opts.select
+                    .iter()
+                    .map(|c| index(c))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        };
+        let headers = match &selection {
+            Some(cols) => cols.iter().map(|&i| &headers[i]).collect(),
+            None => headers.clone(),
+        };
+        Ok(Self {
+            predicates,
+            sort_keys,
+            selection,
+            headers,
+        })
+    }
+
+    /// Column names after selection.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    /// Whether the typed cells of a row satisfy every condition, so `--schema` and
+    /// `--infer-types` decide how cells compare.
+    pub fn matches(&self, cells: &[Value]) -> bool {
+        self.predicates
+            .iter()
+            .all(|(i, p)| p.matches(cells.get(*i).unwrap_or(&Value::Null)))
+    }
+
+    /// Whether rows have to be collected and sorted before they can be written.
+    pub fn sorts(&self) -> bool {
+        !self.sort_keys.is_empty()
+    }
+
+    pub fn compare(&self, a: &[Value], b: &[Value]) -> Ordering {
+        self.sort_keys
+            .iter()
+            .map(|(i, desc)| {
+                let (a, b) = (
+                    a.get(*i).unwrap_or(&Value::Null),
+                    b.get(*i).unwrap_or(&Value::Null),
+                );
+                match (Cell::from_value(a), Cell::from_value(b)) {
+                    // empty cells sort last in either direction
+                    (Cell::Empty, Cell::Empty) => Ordering::Equal,
+                    (Cell::Empty, _) => Ordering::Greater,
+                    (_, Cell::Empty) => Ordering::Less,
+                    (a, b) if *desc => compare(b, a),
+                    (a, b) => compare(a, b),
+                }
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Picks the selected columns, in selection order.
+    pub fn project<T: Clone>(&self, cells: Vec<T>) -> Vec<T> {
+        match &self.selection {
+            Some(cols) => cols.iter().filter_map(|&i| cells.get(i).cloned()).collect(),
+            None => cells,
+        }
+    }
+}
+
+impl Predicate {
+    /// Tests a typed cell. Numbers and booleans compare with the value as such, untyped
+    /// text cells as `compare_cells` does.
+    pub fn matches(&self, cell: &Value) -> bool {
+        let text = match cell {
+            Value::Null => Cow::Borrowed(""),
+            Value::String(s) => Cow::Borrowed(s.as_str()),
+            v => Cow::Owned(v.to_string()),
+        };
+        let cell = Cell::from_value(cell);
+        let order = compare(cell, Cell::new(&self.value));
+        match self.op {
+            Op::Match => self.regex.as_ref().is_some_and(|r| r.is_match(&text)),
+            Op::NotMatch => self.regex.as_ref().is_some_and(|r| !r.is_match(&text)),
+            Op::Eq => order.is_eq(),
+            Op::Ne => order.is_ne(),
+            // an empty cell is neither greater nor less than anything
+            _ if matches!(cell, Cell::Empty) => false,
+            Op::Gt => order.is_gt(),
+            Op::Ge => order.is_ge(),
+            Op::Lt => order.is_lt(),
+            Op::Le => order.is_le(),
+        }
+    }
+}
+
+/// A cell by the kind it is compared as, in the order kinds sort.
+#[derive(Copy, Clone)]
+enum Cell<'a> {
+    Empty,
+    Number(f64),
+    Bool(bool),
+    Text(&'a str),
+}
+
+impl<'a> Cell<'a> {
+    fn new(s: &'a str) -> Self {
+        if s.is_empty() {
+            Cell::Empty
+        } else if let Some(n) = s.parse::<f64>().ok().filter(|n| n.is_finite()) {
+            Cell::Number(n)
+        } else if let Some(b) = parse_bool(s) {
+            Cell::Bool(b)
+        } else {
+            Cell::Text(s)
+        }
+    }
+
+    /// A typed cell keeps its type, while text is classified like a raw cell.
+    fn from_value(value: &'a Value) -> Self {
+        match value {
+            Value::Number(n) => n.as_f64().map_or(Cell::Empty, Cell::Number),
+            Value::Bool(b) => Cell::Bool(*b),
+            Value::String(s) => Cell::new(s),
+            _ => Cell::Empty,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Cell::Empty => 0,
+            Cell::Number(_) => 1,
+            Cell::Bool(_) => 2,
+            Cell::Text(_) => 3,
+        }
+    }
+}
+
+/// Compares two cells in a total order: empty cells first, then numbers, booleans and
+/// text, each compared within its kind. ISO dates compare correctly as text.
+pub fn compare_cells(a: &str, b: &str) -> Ordering {
+    compare(Cell::new(a), Cell::new(b))
+}
+
+fn compare(a: Cell, b: Cell) -> Ordering {
+    match (a, b) {
+        (Cell::Number(x), Cell::Number(y)) => x.total_cmp(&y),
+        (Cell::Bool(x), Cell::Bool(y)) => x.cmp(&y),
+        (Cell::Text(x), Cell::Text(y)) => x.cmp(y),
+        (x, y) => x.rank().cmp(&y.rank()),
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    for q in ['"', '\''] {
+        if let Some(inner) = s.strip_prefix(q).and_then(|s| s.strip_suffix(q)) {
+            return inner;
+        }
+    }
+    s
+}
+
+impl FromStr for Predicate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pos, sym, op) = s
+            .char_indices()
+            .find_map(|(i, _)| {
+                OPERATORS
+                    .iter()
+                    .find(|(sym, _)| s[i..].starts_with(sym))
+                    .map(|(sym, op)| (i, *sym, *op))
+            })
+            .ok_or_else(|| anyhow!("Invalid condition {:?}, expected e.g. \"Kit Number>10\"", s))?;
+        let column = s[..pos].trim();
+        if column.is_empty() {
+            return Err(anyhow!("Missing column in condition {:?}", s));
+        }
+        let value = unquote(s[pos + sym.len()..].trim()).to_string();
+        let regex = match op {
+            Op::Match | Op::NotMatch => Some(Regex::new(&value)?),
+            _ => None,
+        };
+        Ok(Self {
+            column: column.to_string(),
+            op,
+            value,
+            regex,
+        })
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, desc) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (s, false),
+        };
+        Ok(Self {
+            column: column.to_string(),
+            desc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filter(filter: &[&str], sort_by: &[&str], select: &[&str]) -> Result<RowFilter> {
+        let headers = StringRecord::from(vec!["Name", "Position", "Kit Number"]);
+        let opts = CsvFilterOpts {
+            select: select.iter().map(|s| s.to_string()).collect(),
+            filter: filter.iter().map(|s| s.parse()).collect::<Result<_>>()?,
+            sort_by: sort_by.iter().map(|s| s.parse()).collect::<Result<_>>()?,
+        };
+        RowFilter::new(&headers, &opts)
+    }
+
+    #[test]
+    fn test_predicate() -> Result<()> {
+        let p: Predicate = "Kit Number>=10".parse()?;
+        assert_eq!((p.column.as_str(), p.op), ("Kit Number", Op::Ge));
+        assert!(p.matches(&json!("10")) && p.matches(&json!("77")) && !p.matches(&json!("9")));
+        assert!(!p.matches(&json!("")) && !p.matches(&Value::Null));
+        assert!(p.matches(&json!(10.0)) && !p.matches(&json!(9)));
+
+        let p: Predicate = "Position = 'Centre-Back'".parse()?;
+        assert!(p.matches(&json!("Centre-Back")) && !p.matches(&json!("Goalkeeper")));
+        let p: Predicate = "Name!~^G".parse()?;
+        assert!(!p.matches(&json!("Gianluigi Buffon")) && p.matches(&json!("Paulo Dybala")));
+        let p: Predicate = "Captain=true".parse()?;
+        assert!(p.matches(&json!(true)) && !p.matches(&json!(false)));
+        let p: Predicate = "Kit Number~^1".parse()?;
+        assert!(p.matches(&json!(19)) && !p.matches(&Value::Null));
+
+        assert!("Kit Number".parse::<Predicate>().is_err());
+        assert!(">10".parse::<Predicate>().is_err());
+        assert!("Name~[".parse::<Predicate>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_cells() {
+        let mut cells = vec!["b", "10", "true", "", "9", "a", "false", "1e1", "NaN"];
+        cells.sort_by(|a, b| compare_cells(a, b));
+        assert_eq!(
+            cells,
+            ["", "9", "10", "1e1", "false", "true", "NaN", "a", "b"]
+        );
+        assert!(compare_cells("9", "10").is_lt());
+        assert!(compare_cells("10", "9a").is_lt());
+        assert!(compare_cells("9a", "10").is_gt());
+    }
+
+    #[test]
+    fn test_row_filter() -> Result<()> {
+        let rows = [
+            vec![json!("Buffon"), json!("Goalkeeper"), json!(77)],
+            vec![json!("Dybala"), json!("Forward"), json!(10)],
+            vec![json!("Bonucci"), json!("Centre-Back"), json!(19)],
+            vec![json!("Kean"), json!("Forward"), Value::Null],
+        ];
+        let f = filter(&["Kit Number>10"], &[], &["Kit Number", "Name"])?;
+        let matched = rows.iter().filter(|r| f.matches(r)).collect::<Vec<_>>();
+        assert_eq!(matched.len(), 2);
+        assert_eq!(f.headers(), &StringRecord::from(vec!["Kit Number", "Name"]));
+        assert_eq!(
+            f.project(matched[0].to_vec()),
+            vec![json!(77), json!("Buffon")]
+        );
+
+        let f = filter(&[], &["Position", "Kit Number:desc"], &[])?;
+        let mut sorted = rows.to_vec();
+        sorted.sort_by(|a, b| f.compare(a, b));
+        let names = sorted
+            .iter()
+            .map(|r| r[0].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Bonucci", "Dybala", "Kean", "Buffon"]);
+
+        assert!(filter(&["Number>1"], &[], &[]).is_err());
+        assert!(filter(&[], &[], &["Age"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Builds nested objects from dotted column names: `address.city` becomes
+/// `{"address": {"city": ...}}` and `tags[0]`, `tags[1]` become a `tags` array.
+#[derive(Debug)]
+pub struct Nester {
+    paths: Vec<Vec<Segment>>,
+}
+
+impl Nester {
+    /// Parses the column paths, rejecting columns that would overwrite each other, like
+    /// `address` next to `address.city`, and array indices beyond the number of columns.
+    pub fn new<'a>(headers: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let headers = headers.into_iter().collect::<Vec<_>>();
+        let paths = headers
+            .iter()
+            .map(|h| parse_path(h, headers.len()))
+            .collect::<Result<Vec<_>>>()?;
+        for (i, a) in paths.iter().enumerate() {
+            for (j, b) in paths.iter().enumerate().skip(i + 1) {
+                if a.starts_with(b) || b.starts_with(a) || conflicts(a, b) {
+                    return Err(anyhow!(
+                        "Columns {:?} and {:?} cannot both be nested",
+                        headers[i],
+                        headers[j]
+                    ));
+                }
+            }
+        }
+        Ok(Self { paths })
+    }
+
+    /// Nests the cells of a row; array items without a column are null.
+    pub fn nest(&self, cells: Vec<Value>) -> Value {
+        let mut root = Value::Object(Map::new());
+        for (path, cell) in self.paths.iter().zip(cells) {
+            let mut node = &mut root;
+            for segment in path {
+                node = match segment {
+                    Segment::Key(k) => {
+                        if node.is_null() {
+                            *node = Value::Object(Map::new());
+                        }
+                        node.as_object_mut()
+                            .expect("checked in Nester::new")
+                            .entry(k.clone())
+                            .or_insert(Value::Null)
+                    }
+                    Segment::Index(i) => {
+                        if node.is_null() {
+                            *node = Value::Array(Vec::new());
+                        }
+                        let items = node.as_array_mut().expect("checked in Nester::new");
+                        if items.len() <= *i {
+                            items.resize(i + 1, Value::Null);
+                        }
+                        &mut items[*i]
+                    }
+                };
+            }
+            *node = cell;
+        }
+        root
+    }
+}
+
+/// Two paths conflict when, at the first segment where they differ, one indexes an array
+/// and the other names an object key.
+fn conflicts(a: &[Segment], b: &[Segment]) -> bool {
+    a.iter()
+        .zip(b)
+        .find(|(x, y)| x != y)
+        .is_some_and(|(x, y)| std::mem::discriminant(x) != std::mem::discriminant(y))
+}
+
+/// Indices must be below `max_index`: each column fills at most one item, so a larger one
+/// would only pad every row with nulls.
+fn parse_path(header: &str, max_index: usize) -> Result<Vec<Segment>> {
+    let invalid = || anyhow!("Invalid nested column name {:?}", header);
+    let mut path = Vec::new();
+    for part in header.split('.') {
+        let (key, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if key.is_empty() && (path.is_empty() || rest.is_empty()) {
+            return Err(invalid());
+        }
+        if !key.is_empty() {
+            path.push(Segment::Key(key.to_string()));
+        }
+        while let Some(r) = rest.strip_prefix('[') {
+            let (index, tail) = r.split_once(']').ok_or_else(invalid)?;
+            let index = index.parse::<usize>().map_err(|_| invalid())?;
+            if index >= max_index {
+                return Err(anyhow!(
+                    "Array index {} in column {:?} is out of range, it must be below the number of columns ({})",
+                    index,
+                    header,
+                    max_index
+                ));
+            }
+            path.push(Segment::Index(index));
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(invalid());
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_nester() -> Result<()> {
+        let nester = Nester::new([
+            "name",
+            "address.city",
+            "address.zip",
+            "tags[1]",
+            "tags[0]",
+            "kits[0].number",
+        ])?;
+        let row = nester.nest(vec![
+            json!("Buffon"),
+            json!("Turin"),
+            Value::Null,
+            json!("captain"),
+            json!("keeper"),
+            json!(77),
+        ]);
+        assert_eq!(
+            row,
+            json!({
+                "name": "Buffon",
+                "address": { "city": "Turin", "zip": null },
+                "tags": ["keeper", "captain"],
+                "kits": [{ "number": 77 }]
+            })
+        );
+        assert_eq!(
+            Nester::new(["a", "b", "tags[2]"])?.nest(vec![json!(1), json!(2), json!(3)]),
+            json!({ "a": 1, "b": 2, "tags": [null, null, 3] })
+        );
+        assert!(Nester::new(["tags[4000000000]"]).is_err());
+        assert!(Nester::new(["tags[1]"]).is_err());
+
+        assert!(Nester::new(["address", "address.city"]).is_err());
+        assert!(Nester::new(["tags[0]", "tags.first"]).is_err());
+        assert!(Nester::new(["a", "a"]).is_err());
+        assert!(Nester::new(["tags[x]"]).is_err());
+        assert!(Nester::new(["a..b"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+use super::csv_convert::open_csv;
+use crate::cli::{CsvReaderOpts, JoinKind};
+use crate::get_writer;
+use anyhow::{anyhow, Result};
+use csv::{StringRecord, Writer, WriterBuilder};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// `csv split --by` reopens files in append mode rather than exceed the open file limit
+const MAX_OPEN_FILES: usize = 256;
+
+/// Joins two CSV files on the `on` columns, which must exist in both.
+///
+/// The right file is loaded into a hash table and the left one is streamed. Right columns
+/// clashing with a left column name get a `_right` suffix.
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    on: &[String],
+    kind: JoinKind,
+    output: &str,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let (mut left_reader, left_headers) = open_csv(left, opts)?;
+    let (mut right_reader, right_headers) = open_csv(right, opts)?;
+    let left_key = column_indexes(&left_headers, on, left)?;
+    let right_key = column_indexes(&right_headers, on, right)?;
+    let right_rest = (0..right_headers.len())
+        .filter(|i| !right_key.contains(i))
+        .collect::<Vec<_>>();
+
+    let mut headers = left_headers.clone();
+    for &i in &right_rest {
+        let name = &right_headers[i];
+        if left_headers.iter().any(|h| h == name) {
+            headers.push_field(&format!("{}_right", name));
+        } else {
+            headers.push_field(name);
+        }
+    }
+
+    let mut right_rows = Vec::new();
+    let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for result in right_reader.records() {
+        let record = result?;
+        index
+            .entry(key_of(&record, &right_key))
+            .or_default()
+            .push(right_rows.len());
+        right_rows.push(record);
+    }
+
+    let mut writer = csv_writer(output, opts)?;
+    if opts.header {
+        writer.write_record(&headers)?;
+    }
+    let mut matched = vec![false; right_rows.len()];
+    let empty_right = vec![""; right_rest.len()];
+    for result in left_reader.records() {
+        let record = result?;
+        match index.get(&key_of(&record, &left_key)) {
+            Some(rows) => {
+                for &r in rows {
+                    matched[r] = true;
+                    let rest = right_rest.iter().map(|&i| &right_rows[r][i]);
+                    writer.write_record(record.iter().chain(rest))?;
+                }
+            }
+            None if kind != JoinKind::Inner => {
+                writer.write_record(record.iter().chain(empty_right.iter().copied()))?;
+            }
+            None => {}
+        }
+    }
+
+    if kind == JoinKind::Outer {
+        for (r, record) in right_rows.iter().enumerate().filter(|(r, _)| !matched[*r]) {
+            // the key columns of unmatched right rows fill the left key columns
+            let row = (0..left_headers.len()).map(|i| {
+                left_key
+                    .iter()
+                    .position(|&k| k == i)
+                    .map_or("", |k| &record[right_key[k]])
+            });
+            let rest = right_rest.iter().map(|&i| &right_rows[r][i]);
+            writer.write_record(row.chain(rest))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Concatenates CSV files. The output header is the union of all headers in first-seen
+/// order, and each row is mapped onto it by column name.
+///
+/// Every input is opened before any rows are read, so `-` (stdin) may appear once.
+pub fn process_csv_cat(inputs: &[String], output: &str, opts: &CsvReaderOpts) -> Result<()> {
+    if inputs.iter().filter(|i| *i == "-").count() > 1 {
+        return Err(anyhow!("Stdin (-) can only be concatenated once"));
+    }
+    let readers = inputs
+        .iter()
+        .map(|input| open_csv(input, opts))
+        .collect::<Result<Vec<_>>>()?;
+    let mut headers = StringRecord::new();
+    for (_, file_headers) in &readers {
+        for h in file_headers.iter() {
+            if !headers.iter().any(|name| name == h) {
+                headers.push_field(h);
+            }
+        }
+    }
+
+    let mut writer = csv_writer(output, opts)?;
+    if opts.header {
+        writer.write_record(&headers)?;
+    }
+    for (mut reader, file_headers) in readers {
+        let positions = headers
+            .iter()
+            .map(|h| file_headers.iter().position(|f| f == h))
+            .collect::<Vec<_>>();
+        let mut record = StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let row = positions
+                .iter()
+                .map(|p| p.and_then(|i| record.get(i)).unwrap_or_default());
+            writer.write_record(row)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Drops rows whose `by` columns (or whole row when empty) were already seen.
+pub fn process_csv_dedupe(
+    input: &str,
+    by: &[String],
+    output: &str,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let (mut reader, headers) = open_csv(input, opts)?;
+    let columns = if by.is_empty() {
+        (0..headers.len()).collect()
+    } else {
+        column_indexes(&headers, by, input)?
+    };
+
+    let mut writer = csv_writer(output, opts)?;
+    if opts.header {
+        writer.write_record(&headers)?;
+    }
+    let mut seen = HashSet::new();
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        if seen.insert(key_of(&record, &columns)) {
+            writer.write_record(&record)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Splits a CSV file into chunks of `rows` rows, or into one file per distinct value of
+/// the `by` column, repeating the header in each. Returns the files written, in order.
+///
+/// Chunks are named `<stem>_0001.csv`, `<stem>_0002.csv`, ... and value files
+/// `<stem>_<value>.csv`, with characters unsafe in file names replaced by `_`.
+pub fn process_csv_split(
+    input: &str,
+    rows: Option<usize>,
+    by: Option<&str>,
+    output_dir: &str,
+    opts: &CsvReaderOpts,
+) -> Result<Vec<PathBuf>> {
+    let (mut reader, headers) = open_csv(input, opts)?;
+    let by = match by {
+        Some(column) => Some(column_indexes(&headers, &[column.to_string()], input)?[0]),
+        None => None,
+    };
+    let path = Path::new(input);
+    let stem = match path.file_stem() {
+        Some(stem) if input != "-" => stem.to_string_lossy().into_owned(),
+        _ => "stdin".to_string(),
+    };
+    let ext = path
+        .extension()
+        .map_or("csv".into(), |e| e.to_string_lossy());
+    let chunk_path = |name: &str| Path::new(output_dir).join(format!("{}_{}.{}", stem, name, ext));
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut files = Vec::new();
+    let mut record = StringRecord::new();
+    if let Some(column) = by {
+        // file index by value, and writers for the files currently open
+        let mut names: HashMap<String, usize> = HashMap::new();
+        let mut taken = HashSet::new();
+        let mut open: HashMap<usize, Writer<BufWriter<File>>> = HashMap::new();
+        while reader.read_record(&mut record)? {
+            let value = record.get(column).unwrap_or_default();
+            let (index, created) = match names.get(value) {
+                Some(&i) => (i, false),
+                None => {
+                    let name = file_name(value);
+                    let mut path = chunk_path(&name);
+                    let mut n = 1;
+                    while !taken.insert(path.clone()) {
+                        n += 1;
+                        path = chunk_path(&format!("{}_{}", name, n));
+                    }
+                    names.insert(value.to_string(), files.len());
+                    files.push(path);
+                    (files.len() - 1, true)
+                }
+            };
+            if !open.contains_key(&index) {
+                if open.len() == MAX_OPEN_FILES {
+                    for (_, mut w) in open.drain() {
+                        w.flush()?;
+                    }
+                }
+                let file = if created {
+                    File::create(&files[index])?
+                } else {
+                    OpenOptions::new().append(true).open(&files[index])?
+                };
+                let mut writer = chunk_writer(file, opts);
+                if created && opts.header {
+                    writer.write_record(&headers)?;
+                }
+                open.insert(index, writer);
+            }
+            open.get_mut(&index)
+                .expect("opened above")
+                .write_record(&record)?;
+        }
+        for (_, mut w) in open {
+            w.flush()?;
+        }
+    } else {
+        let rows = rows
+            .filter(|&n| n > 0)
+            .ok_or_else(|| anyhow!("--rows must be positive"))?;
+        let mut writer: Option<Writer<BufWriter<File>>> = None;
+        let mut count = 0;
+        while reader.read_record(&mut record)? {
+            if count % rows == 0 {
+                if let Some(mut w) = writer.take() {
+                    w.flush()?;
+                }
+                let path = chunk_path(&format!("{:04}", files.len() + 1));
+                let mut w = chunk_writer(File::create(&path)?, opts);
+                if opts.header {
+                    w.write_record(&headers)?;
+                }
+                writer = Some(w);
+                files.push(path);
+            }
+            writer
+                .as_mut()
+                .expect("created above")
+                .write_record(&record)?;
+            count += 1;
+        }
+        if let Some(mut w) = writer {
+            w.flush()?;
+        }
+    }
+    Ok(files)
+}
+
+fn chunk_writer(file: File, opts: &CsvReaderOpts) -> Writer<BufWriter<File>> {
+    WriterBuilder::new()
+        .delimiter(opts.delimiter)
+        .from_writer(BufWriter::new(file))
+}
+
+fn file_name(value: &str) -> String {
+    if value.is_empty() {
+        return "empty".to_string();
+    }
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn csv_writer(output: &str, opts: &CsvReaderOpts) -> Result<Writer<Box<dyn Write>>> {
+    Ok(WriterBuilder::new()
+        .delimiter(opts.delimiter)
+        .from_writer(get_writer(output)?))
+}
+
+fn column_indexes(headers: &StringRecord, columns: &[String], file: &str) -> Result<Vec<usize>> {
+    columns
+        .iter()
+        .map(|c| {
+            headers
+                .iter()
+                .position(|h| h == c)
+                .ok_or_else(|| anyhow!("Column {:?} not found in {}", c, file))
+        })
+        .collect()
+}
+
+fn key_of(record: &StringRecord, columns: &[usize]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|&i| record.get(i).unwrap_or_default().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn opts() -> CsvReaderOpts {
+        CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        }
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) -> Result<String> {
+        let path = dir.join(name);
+        fs::write(&path, content)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn test_process_csv_join() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let left = write(dir.path(), "left.csv", "Name,Kit\nBuffon,77\nDybala,10\n")?;
+        let right = write(
+            dir.path(),
+            "right.csv",
+            "Name,Kit,Country\nBuffon,1,Italy\nRonaldo,7,Portugal\n",
+        )?;
+        let output = dir.path().join("out.csv");
+        let output = output.to_str().unwrap();
+        let on = ["Name".to_string()];
+
+        process_csv_join(&left, &right, &on, JoinKind::Inner, output, &opts())?;
+        assert_eq!(
+            fs::read_to_string(output)?,
+            "Name,Kit,Kit_right,Country\nBuffon,77,1,Italy\n"
+        );
+        process_csv_join(&left, &right, &on, JoinKind::Left, output, &opts())?;
+        assert_eq!(
+            fs::read_to_string(output)?,
+            "Name,Kit,Kit_right,Country\nBuffon,77,1,Italy\nDybala,10,,\n"
+        );
+        process_csv_join(&left, &right, &on, JoinKind::Outer, output, &opts())?;
+        assert_eq!(
+            fs::read_to_string(output)?,
+            "Name,Kit,Kit_right,Country\nBuffon,77,1,Italy\nDybala,10,,\nRonaldo,,7,Portugal\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_cat_and_dedupe() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let a = write(dir.path(), "a.csv", "Name,Kit\nBuffon,77\nDybala,10\n")?;
+        let b = write(dir.path(), "b.csv", "Country,Name\nItaly,Buffon\n")?;
+        let output = dir.path().join("out.csv");
+        let output = output.to_str().unwrap();
+
+        process_csv_cat(&[a, b], output, &opts())?;
+        assert_eq!(
+            fs::read_to_string(output)?,
+            "Name,Kit,Country\nBuffon,77,\nDybala,10,\nBuffon,,Italy\n"
+        );
+        let err = process_csv_cat(&["-".into(), "-".into()], output, &opts()).unwrap_err();
+        assert_eq!(err.to_string(), "Stdin (-) can only be concatenated once");
+
+        let deduped = dir.path().join("deduped.csv");
+        let deduped = deduped.to_str().unwrap();
+        process_csv_dedupe(output, &["Name".into()], deduped, &opts())?;
+        assert_eq!(
+            fs::read_to_string(deduped)?,
+            "Name,Kit,Country\nBuffon,77,\nDybala,10,\n"
+        );
+        process_csv_dedupe(output, &[], deduped, &opts())?;
+        assert_eq!(fs::read_to_string(deduped)?, fs::read_to_string(output)?);
+        assert!(process_csv_dedupe(output, &["Age".into()], deduped, &opts()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_split() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = write(
+            dir.path(),
+            "players.csv",
+            "Name,Position\nBuffon,Goalkeeper\nDybala,Forward\nPerin,Goalkeeper\nKean,\n",
+        )?;
+        let out = dir.path().join("chunks");
+        let out = out.to_str().unwrap();
+
+        let files = process_csv_split(&input, Some(3), None, out, &opts())?;
+        let names = files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["players_0001.csv", "players_0002.csv"]);
+        assert_eq!(fs::read_to_string(&files[1])?, "Name,Position\nKean,\n");
+
+        let files = process_csv_split(&input, None, Some("Position"), out, &opts())?;
+        let names = files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_string_lossy())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "players_Goalkeeper.csv",
+                "players_Forward.csv",
+                "players_empty.csv"
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&files[0])?,
+            "Name,Position\nBuffon,Goalkeeper\nPerin,Goalkeeper\n"
+        );
+        assert!(process_csv_split(&input, Some(0), None, out, &opts()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,849 @@
+use super::csv_convert::{open_csv, record_writer};
+use super::csv_filter::compare_cells;
+use super::csv_sql::{parse_query, AggFunc, BinaryOp, Expr, SelectItem};
+use super::csv_types::infer_value;
+use crate::cli::{CsvReaderOpts, OutputFormat};
+use crate::get_writer;
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use regex::{Regex, RegexBuilder};
+use serde_json::{Number, Value};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io::BufWriter;
+use std::path::Path;
+
+/// The columns and rows produced by a query.
+#[derive(Debug)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Runs a SQL query over CSV files and writes the result in any output format.
+pub fn process_csv_query(
+    sql: &str,
+    tables: &[String],
+    format: OutputFormat,
+    output: &str,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let result = query_csv(sql, tables, opts)?;
+    let headers = StringRecord::from(result.columns.clone());
+    let mut writer = record_writer(format, BufWriter::new(get_writer(output)?), &headers);
+    for row in result.rows {
+        let record = result.columns.iter().cloned().zip(row).collect();
+        writer.write_record(&Value::Object(record))?;
+    }
+    writer.finish()
+}
+
+/// Runs a SQL query over CSV files.
+///
+/// `tables` are `name=path` or plain paths named after the file stem; a table that is not
+/// listed is looked up as `<name>.csv` in the current directory. Tables are loaded into
+/// memory with inferred cell types.
+pub fn query_csv(sql: &str, tables: &[String], opts: &CsvReaderOpts) -> Result<QueryResult> {
+    let query = parse_query(sql)?;
+    let mut scope = Scope::default();
+    let mut rows = vec![vec![]];
+    let engine = Engine::default();
+
+    if let Some(from) = &query.from {
+        let (columns, table_rows) = load_table(&from.name, tables, opts)?;
+        scope.add(from.qualifier(), columns);
+        rows = table_rows;
+    }
+    for join in query.joins {
+        let (columns, right_rows) = load_table(&join.table.name, tables, opts)?;
+        let width = columns.len();
+        scope.add(join.table.qualifier(), columns);
+        let on = scope.bind(join.on)?;
+        let mut joined = Vec::new();
+        for left in rows {
+            let mut matched = false;
+            for right in &right_rows {
+                let row = left.iter().chain(right).cloned().collect::<Vec<_>>();
+                if engine.truth(&on, &Ctx::row(&row))? == Some(true) {
+                    joined.push(row);
+                    matched = true;
+                }
+            }
+            if join.left && !matched {
+                let mut row = left;
+                row.resize(row.len() + width, Value::Null);
+                joined.push(row);
+            }
+        }
+        rows = joined;
+    }
+
+    if let Some(filter) = query.filter {
+        let filter = scope.bind(filter)?;
+        let mut kept = Vec::with_capacity(rows.len());
+        for row in rows {
+            if engine.truth(&filter, &Ctx::row(&row))? == Some(true) {
+                kept.push(row);
+            }
+        }
+        rows = kept;
+    }
+
+    let mut columns = Vec::new();
+    let mut items = Vec::new();
+    for item in query.items {
+        match item {
+            SelectItem::Wildcard(table) => {
+                let found = scope.columns(table.as_deref());
+                if found.is_empty() {
+                    return Err(anyhow!("No columns match {}.*", table.unwrap_or_default()));
+                }
+                for (i, name) in found {
+                    columns.push(name);
+                    items.push(Expr::Index(i));
+                }
+            }
+            SelectItem::Expr { expr, name } => {
+                columns.push(name);
+                items.push(scope.bind(expr)?);
+            }
+        }
+    }
+    let group_by = query
+        .group_by
+        .into_iter()
+        .map(|e| scope.bind(e))
+        .collect::<Result<Vec<_>>>()?;
+    let having = query
+        .having
+        .map(|e| scope.bind_output(e, &columns))
+        .transpose()?;
+    let order_by = query
+        .order_by
+        .into_iter()
+        .map(|(e, desc)| Ok((scope.bind_order(e, &columns)?, desc)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // each output row is evaluated against a source row and, when aggregating, its group
+    let aggregate = !group_by.is_empty()
+        || having.is_some()
+        || items.iter().any(Expr::has_aggregate)
+        || order_by.iter().any(|(e, _)| e.has_aggregate());
+    let groups: Vec<Vec<usize>> = if aggregate {
+        let mut index = HashMap::new();
+        let mut groups = Vec::new();
+        if group_by.is_empty() {
+            groups.push((0..rows.len()).collect());
+        }
+        for (i, row) in rows.iter().enumerate().filter(|_| !group_by.is_empty()) {
+            let key = group_by
+                .iter()
+                .map(|e| engine.eval(e, &Ctx::row(row)))
+                .collect::<Result<Vec<_>>>()?;
+            let g = *index
+                .entry(Value::Array(key).to_string())
+                .or_insert_with(|| {
+                    groups.push(vec![]);
+                    groups.len() - 1
+                });
+            groups[g].push(i);
+        }
+        groups
+    } else {
+        (0..rows.len()).map(|i| vec![i]).collect()
+    };
+
+    let empty = vec![Value::Null; scope.names.len()];
+    let mut results = Vec::new();
+    let mut seen = HashSet::new();
+    for group in groups {
+        let members = group
+            .iter()
+            .map(|&i| rows[i].as_slice())
+            .collect::<Vec<_>>();
+        let ctx = Ctx {
+            row: members.first().copied().unwrap_or(&empty),
+            group: aggregate.then_some(members.as_slice()),
+            output: &[],
+        };
+        let output = items
+            .iter()
+            .map(|e| engine.eval(e, &ctx))
+            .collect::<Result<Vec<_>>>()?;
+        let ctx = Ctx {
+            output: &output,
+            ..ctx
+        };
+        if let Some(having) = &having {
+            if engine.truth(having, &ctx)? != Some(true) {
+                continue;
+            }
+        }
+        if query.distinct && !seen.insert(Value::Array(output.clone()).to_string()) {
+            continue;
+        }
+        let keys = order_by
+            .iter()
+            .map(|(e, _)| engine.eval(e, &ctx))
+            .collect::<Result<Vec<_>>>()?;
+        results.push((keys, output));
+    }
+
+    results.sort_by(|(a, _), (b, _)| {
+        a.iter()
+            .zip(b)
+            .zip(&order_by)
+            .map(|((x, y), (_, desc))| match (x.is_null(), y.is_null()) {
+                // nulls last either way, like empty cells in `--sort-by`
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                _ if *desc => compare_values(y, x),
+                _ => compare_values(x, y),
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    let rows = results
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|(_, output)| output)
+        .collect();
+    Ok(QueryResult { columns, rows })
+}
+
+fn load_table(
+    name: &str,
+    tables: &[String],
+    opts: &CsvReaderOpts,
+) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+    let path = tables
+        .iter()
+        .find_map(|t| match t.split_once('=') {
+            Some((n, path)) => (n == name).then_some(path.to_string()),
+            None => (Path::new(t).file_stem()? == name).then(|| t.clone()),
+        })
+        .or_else(|| {
+            let path = format!("{}.csv", name);
+            Path::new(&path).exists().then_some(path)
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown table {}, pass it with --table {}=<file>",
+                name,
+                name
+            )
+        })?;
+    let (mut reader, headers) = open_csv(&path, opts)?;
+    let mut rows = Vec::new();
+    for result in reader.records() {
+        let record = result?;
+        let mut row = record
+            .iter()
+            .map(|cell| infer_value(cell, false))
+            .collect::<Vec<_>>();
+        row.resize(headers.len(), Value::Null);
+        rows.push(row);
+    }
+    Ok((headers.iter().map(String::from).collect(), rows))
+}
+
+/// The qualified column names of the joined tables.
+#[derive(Debug, Default)]
+struct Scope {
+    names: Vec<(String, String)>,
+}
+
+impl Scope {
+    fn add(&mut self, table: &str, columns: Vec<String>) {
+        self.names
+            .extend(columns.into_iter().map(|c| (table.to_string(), c)));
+    }
+
+    /// The columns selected by `*` or `table.*`; duplicate names are qualified.
+    fn columns(&self, table: Option<&str>) -> Vec<(usize, String)> {
+        self.names
+            .iter()
+            .enumerate()
+            .filter(|(_, (t, _))| table.is_none_or(|table| t == table))
+            .map(|(i, (t, c))| {
+                if self.names.iter().filter(|(_, n)| n == c).count() > 1 {
+                    (i, format!("{}.{}", t, c))
+                } else {
+                    (i, c.clone())
+                }
+            })
+            .collect()
+    }
+
+    fn lookup(&self, table: Option<&str>, name: &str) -> Result<usize> {
+        let find = |eq: &dyn Fn(&str, &str) -> bool| {
+            self.names
+                .iter()
+                .enumerate()
+                .filter(|(_, (t, c))| table.is_none_or(|table| eq(t, table)) && eq(c, name))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+        let mut found = find(&|a, b| a == b);
+        if found.is_empty() {
+            found = find(&|a, b| a.eq_ignore_ascii_case(b));
+        }
+        let column = match table {
+            Some(t) => format!("{}.{}", t, name),
+            None => name.to_string(),
+        };
+        match found[..] {
+            [i] => Ok(i),
+            [] => Err(anyhow!("Unknown column {}", column)),
+            _ => Err(anyhow!("Column {} is ambiguous", column)),
+        }
+    }
+
+    /// Resolves column names to row positions and checks function names and aggregates.
+    fn bind(&self, expr: Expr) -> Result<Expr> {
+        expr.rewrite(&mut |e| match e {
+            Expr::Aggregate { arg: Some(arg), .. } if arg.has_aggregate() => {
+                Err(anyhow!("Aggregate functions cannot be nested"))
+            }
+            Expr::Column { table, name } => Ok(Expr::Index(self.lookup(table.as_deref(), &name)?)),
+            Expr::Function { name, .. } if !FUNCTIONS.contains(&name.as_str()) => {
+                Err(anyhow!("Unknown function {}", name))
+            }
+            e => Ok(e),
+        })
+    }
+
+    /// Binds an `ORDER BY` expression, which may name a select item or its position.
+    fn bind_order(&self, expr: Expr, columns: &[String]) -> Result<Expr> {
+        match &expr {
+            Expr::Literal(Value::Number(n)) => match n.as_u64() {
+                Some(i) if (1..=columns.len() as u64).contains(&i) => {
+                    Ok(Expr::Output(i as usize - 1))
+                }
+                _ => Err(anyhow!("ORDER BY position {} is out of range", n)),
+            },
+            _ => self.bind_output(expr, columns),
+        }
+    }
+
+    /// Binds a `HAVING` or `ORDER BY` expression, in which the names of select items refer
+    /// to their values, except inside aggregates.
+    fn bind_output(&self, expr: Expr, columns: &[String]) -> Result<Expr> {
+        let expr = expr.rewrite(&mut |e| match e {
+            Expr::Column { table: None, name } => match columns.iter().position(|c| *c == name) {
+                Some(i) => Ok(Expr::Output(i)),
+                None => Ok(Expr::Column { table: None, name }),
+            },
+            // aggregate arguments are evaluated per row, so names there are table columns
+            Expr::Aggregate { .. } => e.rewrite(&mut |e| match e {
+                Expr::Output(i) => Ok(Expr::Column {
+                    table: None,
+                    name: columns[i].clone(),
+                }),
+                e => Ok(e),
+            }),
+            e => Ok(e),
+        })?;
+        self.bind(expr)
+    }
+}
+
+const FUNCTIONS: &[&str] = &[
+    "lower", "upper", "length", "trim", "abs", "round", "coalesce",
+];
+
+/// What an expression is evaluated against.
+#[derive(Clone, Copy)]
+struct Ctx<'a> {
+    row: &'a [Value],
+    /// The rows of the current group, when aggregating
+    group: Option<&'a [&'a [Value]]>,
+    /// The evaluated select items, for `HAVING` and `ORDER BY`
+    output: &'a [Value],
+}
+
+impl<'a> Ctx<'a> {
+    fn row(row: &'a [Value]) -> Self {
+        Self {
+            row,
+            group: None,
+            output: &[],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Engine {
+    // compiled LIKE patterns
+    patterns: RefCell<HashMap<String, Regex>>,
+}
+
+impl Engine {
+    /// Three-valued truth of a condition: `None` for null.
+    fn truth(&self, expr: &Expr, ctx: &Ctx) -> Result<Option<bool>> {
+        Ok(truth(&self.eval(expr, ctx)?))
+    }
+
+    fn eval(&self, expr: &Expr, ctx: &Ctx) -> Result<Value> {
+        let value = match expr {
+            Expr::Literal(v) => v.clone(),
+            Expr::Index(i) => ctx.row.get(*i).cloned().unwrap_or(Value::Null),
+            Expr::Output(i) => ctx.output.get(*i).cloned().unwrap_or(Value::Null),
+            Expr::Column { name, .. } => return Err(anyhow!("Unbound column {}", name)),
+            Expr::Neg(e) => match number(&self.eval(e, ctx)?)? {
+                None => Value::Null,
+                Some(n) => match n.as_i64() {
+                    Some(i) => i.checked_neg().map_or(Value::Null, Value::from),
+                    None => float(-n.as_f64().unwrap_or_default()),
+                },
+            },
+            Expr::Not(e) => self.truth(e, ctx)?.map_or(Value::Null, |b| Value::Bool(!b)),
+            Expr::Binary { op, left, right } => self.binary(*op, left, right, ctx)?,
+            Expr::IsNull { expr, negated } => {
+                Value::Bool(self.eval(expr, ctx)?.is_null() != *negated)
+            }
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => {
+                let (value, pattern) = (self.eval(expr, ctx)?, self.eval(pattern, ctx)?);
+                if value.is_null() || pattern.is_null() {
+                    Value::Null
+                } else {
+                    Value::Bool(self.like(&text(&value), &text(&pattern))? != *negated)
+                }
+            }
+            Expr::In {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = self.eval(expr, ctx)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                let mut found = Some(false);
+                for item in list {
+                    match compare(&value, &self.eval(item, ctx)?) {
+                        Some(Ordering::Equal) => {
+                            found = Some(true);
+                            break;
+                        }
+                        None => found = None,
+                        Some(_) => {}
+                    }
+                }
+                found.map_or(Value::Null, |b| Value::Bool(b != *negated))
+            }
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let value = self.eval(expr, ctx)?;
+                let (low, high) = (self.eval(low, ctx)?, self.eval(high, ctx)?);
+                match (compare(&value, &low), compare(&value, &high)) {
+                    (Some(l), Some(h)) => Value::Bool((l.is_ge() && h.is_le()) != *negated),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Function { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|e| self.eval(e, ctx))
+                    .collect::<Result<Vec<_>>>()?;
+                function(name, args)?
+            }
+            Expr::Aggregate {
+                func,
+                arg,
+                distinct,
+            } => {
+                let group = ctx.group.ok_or_else(|| {
+                    anyhow!("Aggregate functions are not allowed in WHERE, ON or GROUP BY")
+                })?;
+                self.aggregate(*func, arg.as_deref(), *distinct, group)?
+            }
+        };
+        Ok(value)
+    }
+
+    fn binary(&self, op: BinaryOp, left: &Expr, right: &Expr, ctx: &Ctx) -> Result<Value> {
+        match op {
+            BinaryOp::And | BinaryOp::Or => {
+                let l = self.truth(left, ctx)?;
+                // short-circuit like SQL: false AND x is false, true OR x is true
+                if l == Some(op == BinaryOp::Or) {
+                    return Ok(Value::Bool(op == BinaryOp::Or));
+                }
+                let r = self.truth(right, ctx)?;
+                let value = match (op, l, r) {
+                    (BinaryOp::And, Some(true), r) | (BinaryOp::Or, Some(false), r) => r,
+                    (BinaryOp::And, None, Some(false)) => Some(false),
+                    (BinaryOp::Or, None, Some(true)) => Some(true),
+                    _ => None,
+                };
+                return Ok(value.map_or(Value::Null, Value::Bool));
+            }
+            _ => {}
+        }
+        let (l, r) = (self.eval(left, ctx)?, self.eval(right, ctx)?);
+        let cmp =
+            |f: fn(Ordering) -> bool| compare(&l, &r).map_or(Value::Null, |o| Value::Bool(f(o)));
+        let value = match op {
+            BinaryOp::Eq => cmp(Ordering::is_eq),
+            BinaryOp::NotEq => cmp(Ordering::is_ne),
+            BinaryOp::Lt => cmp(Ordering::is_lt),
+            BinaryOp::LtEq => cmp(Ordering::is_le),
+            BinaryOp::Gt => cmp(Ordering::is_gt),
+            BinaryOp::GtEq => cmp(Ordering::is_ge),
+            BinaryOp::Concat if l.is_null() || r.is_null() => Value::Null,
+            BinaryOp::Concat => Value::String(text(&l) + &text(&r)),
+            _ => arithmetic(op, &l, &r)?,
+        };
+        Ok(value)
+    }
+
+    fn aggregate(
+        &self,
+        func: AggFunc,
+        arg: Option<&Expr>,
+        distinct: bool,
+        group: &[&[Value]],
+    ) -> Result<Value> {
+        let Some(arg) = arg else {
+            return Ok(Value::from(group.len()));
+        };
+        let mut values = Vec::with_capacity(group.len());
+        let mut seen = HashSet::new();
+        for row in group {
+            let value = self.eval(arg, &Ctx::row(row))?;
+            if !value.is_null() && (!distinct || seen.insert(value.to_string())) {
+                values.push(value);
+            }
+        }
+        let value = match func {
+            AggFunc::Count => Value::from(values.len()),
+            AggFunc::Sum | AggFunc::Avg if values.is_empty() => Value::Null,
+            AggFunc::Sum => values
+                .iter()
+                .try_fold(Value::from(0), |sum, v| arithmetic(BinaryOp::Add, &sum, v))?,
+            AggFunc::Avg => {
+                let mut sum = 0.0;
+                for v in &values {
+                    sum += number(v)?.and_then(|n| n.as_f64()).unwrap_or_default();
+                }
+                float(sum / values.len() as f64)
+            }
+            AggFunc::Min => values
+                .into_iter()
+                .min_by(compare_values)
+                .unwrap_or(Value::Null),
+            AggFunc::Max => values
+                .into_iter()
+                .max_by(compare_values)
+                .unwrap_or(Value::Null),
+        };
+        Ok(value)
+    }
+
+    /// SQL `LIKE`: `%` matches any run of characters, `_` a single one, ignoring ASCII case.
+    fn like(&self, value: &str, pattern: &str) -> Result<bool> {
+        let mut patterns = self.patterns.borrow_mut();
+        if !patterns.contains_key(pattern) {
+            let re = pattern
+                .chars()
+                .map(|c| match c {
+                    '%' => ".*".to_string(),
+                    '_' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect::<String>();
+            let re = RegexBuilder::new(&format!("^{}$", re))
+                .case_insensitive(true)
+                .dot_matches_new_line(true)
+                .build()?;
+            patterns.insert(pattern.to_string(), re);
+        }
+        Ok(patterns[pattern].is_match(value))
+    }
+}
+
+fn function(name: &str, args: Vec<Value>) -> Result<Value> {
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{}() takes {} argument(s), got {}",
+                name,
+                n,
+                args.len()
+            ))
+        }
+    };
+    if name == "coalesce" {
+        return Ok(args
+            .into_iter()
+            .find(|v| !v.is_null())
+            .unwrap_or(Value::Null));
+    }
+    if name == "round" {
+        if args.is_empty() || args.len() > 2 {
+            return Err(anyhow!(
+                "round() takes 1 or 2 arguments, got {}",
+                args.len()
+            ));
+        }
+    } else {
+        arity(1)?;
+    }
+    if args[0].is_null() {
+        return Ok(Value::Null);
+    }
+    let value = match name {
+        "lower" => Value::String(text(&args[0]).to_lowercase()),
+        "upper" => Value::String(text(&args[0]).to_uppercase()),
+        "trim" => Value::String(text(&args[0]).trim().to_string()),
+        "length" => Value::from(text(&args[0]).chars().count()),
+        "abs" => match number(&args[0])? {
+            Some(n) if n.is_i64() => n
+                .as_i64()
+                .and_then(i64::checked_abs)
+                .map_or(Value::Null, Value::from),
+            n => float(n.and_then(|n| n.as_f64()).unwrap_or_default().abs()),
+        },
+        "round" => {
+            let digits = match args.get(1) {
+                Some(d) => number(d)?.and_then(|n| n.as_i64()).unwrap_or_default(),
+                None => 0,
+            };
+            let n = number(&args[0])?
+                .and_then(|n| n.as_f64())
+                .unwrap_or_default();
+            let scale = 10f64.powi(digits.clamp(-15, 15) as i32);
+            let rounded = (n * scale).round() / scale;
+            if digits <= 0 && rounded.abs() < i64::MAX as f64 {
+                Value::from(rounded as i64)
+            } else {
+                float(rounded)
+            }
+        }
+        _ => return Err(anyhow!("Unknown function {}", name)),
+    };
+    Ok(value)
+}
+
+fn arithmetic(op: BinaryOp, l: &Value, r: &Value) -> Result<Value> {
+    let (Some(a), Some(b)) = (number(l)?, number(r)?) else {
+        return Ok(Value::Null);
+    };
+    if let (Some(x), Some(y), true) = (a.as_i64(), b.as_i64(), op != BinaryOp::Div) {
+        let value = match op {
+            BinaryOp::Add => x.checked_add(y),
+            BinaryOp::Sub => x.checked_sub(y),
+            BinaryOp::Mul => x.checked_mul(y),
+            _ => x.checked_rem(y),
+        };
+        return Ok(value.map_or(Value::Null, Value::from));
+    }
+    let (x, y) = (
+        a.as_f64().unwrap_or_default(),
+        b.as_f64().unwrap_or_default(),
+    );
+    let value = match op {
+        BinaryOp::Add => x + y,
+        BinaryOp::Sub => x - y,
+        BinaryOp::Mul => x * y,
+        // division by zero is null, like in SQLite
+        BinaryOp::Div if y == 0.0 => return Ok(Value::Null),
+        BinaryOp::Div => x / y,
+        _ if y == 0.0 => return Ok(Value::Null),
+        _ => x % y,
+    };
+    Ok(float(value))
+}
+
+/// A numeric value; numeric strings are converted, other strings are an error.
+fn number(value: &Value) -> Result<Option<Number>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Number(n) => Ok(Some(n.clone())),
+        Value::String(s) => {
+            let n = match s.trim().parse::<i64>() {
+                Ok(i) => Some(Number::from(i)),
+                Err(_) => s.trim().parse::<f64>().ok().and_then(Number::from_f64),
+            };
+            n.map(Some)
+                .ok_or_else(|| anyhow!("Cannot use {:?} as a number", s))
+        }
+        v => Err(anyhow!("Cannot use {} as a number", v)),
+    }
+}
+
+fn float(n: f64) -> Value {
+    Number::from_f64(n).map_or(Value::Null, Value::Number)
+}
+
+fn truth(value: &Value) -> Option<bool> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some(*b),
+        Value::Number(n) => Some(n.as_f64() != Some(0.0)),
+        Value::String(s) => Some(!s.is_empty()),
+        _ => Some(true),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Compares two values like `--sort-by` compares cells; `None` if either is null.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    if a.is_null() || b.is_null() {
+        return None;
+    }
+    Some(compare_cells(&text(a), &text(b)))
+}
+
+/// A total order of values, nulls first like empty cells.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    let cell = |v: &Value| if v.is_null() { String::new() } else { text(v) };
+    compare_cells(&cell(a), &cell(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(sql: &str) -> Result<QueryResult> {
+        let opts = CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        };
+        query_csv(sql, &["assets/juventus.csv".into()], &opts)
+    }
+
+    #[test]
+    fn test_query_csv_group_by() -> Result<()> {
+        let ret = query(
+            "SELECT Position, count(*) AS players, max(\"Kit Number\") top FROM juventus \
+             WHERE Position LIKE '%back' GROUP BY Position ORDER BY players DESC, Position",
+        )?;
+        assert_eq!(ret.columns, ["Position", "players", "top"]);
+        assert_eq!(
+            ret.rows,
+            vec![
+                vec![json!("Centre-Back"), json!(5), json!(28)],
+                vec![json!("Right-Back"), json!(2), json!(13)],
+                vec![json!("Left-Back"), json!(1), json!(12)],
+            ]
+        );
+
+        // aliases in HAVING, but not inside its aggregates
+        let ret = query(
+            "SELECT Position, count(*) n, min(Name) Name FROM juventus GROUP BY Position \
+             HAVING n > 3 AND max(Name) <> Name ORDER BY -n",
+        )?;
+        assert_eq!(
+            ret.rows,
+            vec![
+                vec![json!("Central Midfield"), json!(6), json!("Aaron Ramsey")],
+                vec![json!("Centre-Back"), json!(5), json!("Daniele Rugani")],
+                vec![json!("Goalkeeper"), json!(4), json!("Carlo Pinsoglio")],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_csv_select() -> Result<()> {
+        let ret = query(
+            "SELECT upper(Name) || '!' shout, \"Kit Number\" * 2 FROM juventus j \
+             WHERE j.\"Kit Number\" IN (1, 10) OR Name = 'nobody' ORDER BY 2 DESC LIMIT 1",
+        )?;
+        assert_eq!(ret.columns, ["shout", "\"Kit Number\" * 2"]);
+        assert_eq!(ret.rows, vec![vec![json!("PAULO DYBALA!"), json!(20)]]);
+
+        let ret = query("SELECT count(*), avg(\"Kit Number\") FROM juventus WHERE 1 = 0")?;
+        assert_eq!(ret.rows, vec![vec![json!(0), Value::Null]]);
+
+        let ret = query("SELECT DISTINCT Nationality FROM juventus ORDER BY Nationality LIMIT 2")?;
+        assert_eq!(
+            ret.rows,
+            vec![vec![json!("Argentina")], vec![json!("Bosnia-Herzegovina")]]
+        );
+
+        let ret = query("SELECT 7 / 2, 7 % 2, -(1 + 1), null IS NULL, 'a' < 'b' AND NOT false")?;
+        assert_eq!(
+            ret.rows,
+            vec![vec![
+                json!(3.5),
+                json!(1),
+                json!(-2),
+                json!(true),
+                json!(true)
+            ]]
+        );
+
+        let mut values = vec![json!("b"), json!(10), json!(true), Value::Null, json!("9")];
+        values.sort_by(compare_values);
+        assert_eq!(
+            values,
+            [Value::Null, json!("9"), json!(10), json!(true), json!("b")]
+        );
+
+        assert!(query("SELECT Age FROM juventus").is_err());
+        assert!(query("SELECT Name FROM players").is_err());
+        assert!(query("SELECT Name FROM juventus WHERE count(*) > 1").is_err());
+        let err = query("SELECT sum(count(*)) FROM juventus").unwrap_err();
+        assert_eq!(err.to_string(), "Aggregate functions cannot be nested");
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_csv_join() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let countries = dir.path().join("countries.csv");
+        std::fs::write(
+            &countries,
+            "Country,Continent\nItaly,Europe\nArgentina,South America\n",
+        )?;
+        let tables = [
+            "players=assets/juventus.csv".to_string(),
+            countries.to_string_lossy().into_owned(),
+        ];
+        let opts = CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        };
+        let ret = query_csv(
+            "SELECT c.Continent, count(*) n FROM players p \
+             LEFT JOIN countries c ON p.Nationality = c.Country \
+             GROUP BY c.Continent ORDER BY n DESC",
+            &tables,
+            &opts,
+        )?;
+        assert_eq!(
+            ret.rows,
+            vec![
+                vec![Value::Null, json!(17)],
+                vec![json!("Europe"), json!(8)],
+                vec![json!("South America"), json!(2)],
+            ]
+        );
+        Ok(())
+    }
+}
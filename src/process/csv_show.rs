@@ -0,0 +1,224 @@
+use super::csv_convert::open_csv;
+use crate::cli::CsvReaderOpts;
+use anyhow::Result;
+use csv::StringRecord;
+use std::collections::VecDeque;
+use std::io::Write;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Narrowest a column gets when shrinking a table to fit the terminal.
+const MIN_COLUMN_WIDTH: usize = 5;
+
+/// How a table is laid out when rendered as text.
+#[derive(Debug, Clone)]
+pub struct TableLayout {
+    /// Cells wider than this are truncated with an ellipsis
+    pub max_cell_width: usize,
+    /// Total line width to fit into, usually the terminal width
+    pub max_width: Option<usize>,
+}
+
+/// Which records of the input to show.
+#[derive(Debug, Copy, Clone)]
+pub enum RowWindow {
+    All,
+    Head(usize),
+    Tail(usize),
+}
+
+/// Renders a CSV file as an aligned text table, prefixed with 1-based row numbers.
+pub fn process_csv_show(
+    input: &str,
+    opts: &CsvReaderOpts,
+    window: RowWindow,
+    row_numbers: bool,
+    layout: &TableLayout,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let (mut reader, headers) = open_csv(input, opts)?;
+
+    // only the last `n` records are kept around for --tail
+    let mut rows = VecDeque::new();
+    let mut record = StringRecord::new();
+    let mut row = 0;
+    while reader.read_record(&mut record)? {
+        row += 1;
+        match window {
+            RowWindow::Head(n) if rows.len() >= n => break,
+            RowWindow::Tail(0) => continue,
+            RowWindow::Tail(n) if rows.len() >= n => {
+                rows.pop_front();
+            }
+            _ => {}
+        }
+        let mut cells = Vec::with_capacity(record.len() + 1);
+        if row_numbers {
+            cells.push(row.to_string());
+        }
+        cells.extend(record.iter().map(String::from));
+        rows.push_back(cells);
+    }
+
+    let mut columns = Vec::with_capacity(headers.len() + 1);
+    if row_numbers {
+        columns.push("#".to_string());
+    }
+    columns.extend(headers.iter().map(String::from));
+    render_table(&columns, rows.make_contiguous(), layout, out)
+}
+
+/// Writes rows as an aligned table with a header line. Numeric cells are right aligned.
+pub fn render_table(
+    headers: &[String],
+    rows: &[Vec<String>],
+    layout: &TableLayout,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let mut widths = headers.iter().map(|h| h.width()).collect::<Vec<_>>();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            if i < widths.len() {
+                widths[i] = widths[i].max(cell.width());
+            }
+        }
+    }
+    for w in widths.iter_mut() {
+        *w = (*w).min(layout.max_cell_width.max(1));
+    }
+    if let Some(max_width) = layout.max_width {
+        fit_widths(&mut widths, max_width);
+    }
+
+    write_line(out, headers, &widths)?;
+    let rule = widths
+        .iter()
+        .map(|w| "-".repeat(*w))
+        .collect::<Vec<_>>()
+        .join("-+-");
+    writeln!(out, "{}", rule)?;
+    for row in rows {
+        write_line(out, row, &widths)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Shrinks the widest columns until the table fits into `max_width`.
+fn fit_widths(widths: &mut [usize], max_width: usize) {
+    let separators = widths.len().saturating_sub(1) * 3;
+    while widths.iter().sum::<usize>() + separators > max_width {
+        let Some(widest) = widths
+            .iter_mut()
+            .filter(|w| **w > MIN_COLUMN_WIDTH)
+            .max_by_key(|w| **w)
+        else {
+            break;
+        };
+        *widest -= 1;
+    }
+}
+
+fn write_line(out: &mut dyn Write, cells: &[String], widths: &[usize]) -> Result<()> {
+    let line = widths
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let cell = cells.get(i).map_or("", String::as_str);
+            let text = truncate(cell, *w);
+            let pad = " ".repeat(w - text.width());
+            if is_numeric(cell) {
+                format!("{}{}", pad, text)
+            } else {
+                format!("{}{}", text, pad)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" | ");
+    writeln!(out, "{}", line.trim_end())?;
+    Ok(())
+}
+
+/// Cuts a cell to at most `width` columns, marking the cut with an ellipsis.
+fn truncate(cell: &str, width: usize) -> String {
+    // line breaks inside quoted cells would break the table
+    let cell = cell.replace(['\r', '\n'], " ");
+    if cell.width() <= width {
+        return cell;
+    }
+    let mut ret = String::new();
+    let mut used = 0;
+    for c in cell.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        ret.push(c);
+        used += w;
+    }
+    ret.push('…');
+    ret
+}
+
+fn is_numeric(cell: &str) -> bool {
+    !cell.is_empty() && cell.parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(window: RowWindow, layout: &TableLayout) -> Result<String> {
+        let opts = CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        };
+        let mut buf = Vec::new();
+        process_csv_show("assets/juventus.csv", &opts, window, true, layout, &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_process_csv_show() -> Result<()> {
+        let layout = TableLayout {
+            max_cell_width: 12,
+            max_width: None,
+        };
+        let ret = show(RowWindow::Head(2), &layout)?;
+        let lines = ret.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            "# | Name         | Position   | DOB          | Nationality | Kit Number"
+        );
+        assert_eq!(
+            lines[2],
+            "1 | Wojciech Sz… | Goalkeeper | Apr 18, 199… | Poland      |          1"
+        );
+
+        let ret = show(RowWindow::Tail(1), &layout)?;
+        let last = ret.lines().last().unwrap();
+        assert_eq!(
+            last,
+            "27 | Mario Mandz… | Centre-Forw… | May 21, 198… | Croatia     |         17"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fit_widths() {
+        let mut widths = vec![2, 20, 30];
+        fit_widths(&mut widths, 40);
+        assert_eq!(widths, vec![2, 16, 16]);
+        let mut widths = vec![10, 10];
+        fit_widths(&mut widths, 5);
+        assert_eq!(widths, vec![MIN_COLUMN_WIDTH, MIN_COLUMN_WIDTH]);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Juventus", 10), "Juventus");
+        assert_eq!(truncate("Juventus", 5), "Juve…");
+        assert_eq!(truncate("尤文图斯", 5), "尤文…");
+    }
+}
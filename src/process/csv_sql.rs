@@ -0,0 +1,881 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// The subset of SQL understood by `csv query`.
+#[derive(Debug, Default)]
+pub struct Query {
+    pub distinct: bool,
+    pub items: Vec<SelectItem>,
+    pub from: Option<TableRef>,
+    pub joins: Vec<Join>,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    /// Sort expressions, `true` for descending
+    pub order_by: Vec<(Expr, bool)>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+#[derive(Debug)]
+pub enum SelectItem {
+    /// `*` or `table.*`
+    Wildcard(Option<String>),
+    /// An expression and its output column name: the alias, or the expression text
+    Expr { expr: Expr, name: String },
+}
+
+#[derive(Debug)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+impl TableRef {
+    /// The name columns of this table are qualified with.
+    pub fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Join {
+    pub table: TableRef,
+    pub left: bool,
+    pub on: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    /// A column resolved to its position in the joined row
+    Index(usize),
+    /// A `HAVING` or `ORDER BY` reference to a select item, by alias or position
+    Output(usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    In {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `None` as argument stands for `count(*)`
+    Aggregate {
+        func: AggFunc,
+        arg: Option<Box<Expr>>,
+        distinct: bool,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Expr {
+    /// Whether the expression contains an aggregate function.
+    pub fn has_aggregate(&self) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| found |= matches!(e, Expr::Aggregate { .. }));
+        found
+    }
+
+    /// Calls `f` on this expression and all of its sub-expressions.
+    pub fn visit(&self, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        match self {
+            Expr::Neg(e) | Expr::Not(e) | Expr::IsNull { expr: e, .. } => e.visit(f),
+            Expr::Binary { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Expr::Like { expr, pattern, .. } => {
+                expr.visit(f);
+                pattern.visit(f);
+            }
+            Expr::In { expr, list, .. } => {
+                expr.visit(f);
+                list.iter().for_each(|e| e.visit(f));
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                expr.visit(f);
+                low.visit(f);
+                high.visit(f);
+            }
+            Expr::Function { args, .. } => args.iter().for_each(|e| e.visit(f)),
+            Expr::Aggregate { arg: Some(e), .. } => e.visit(f),
+            _ => {}
+        }
+    }
+
+    /// Rewrites this expression bottom-up, e.g. to resolve column names.
+    pub fn rewrite(self, f: &mut impl FnMut(Expr) -> Result<Expr>) -> Result<Expr> {
+        let mut sub = |e: Box<Expr>| e.rewrite(f).map(Box::new);
+        let expr = match self {
+            Expr::Neg(e) => Expr::Neg(sub(e)?),
+            Expr::Not(e) => Expr::Not(sub(e)?),
+            Expr::Binary { op, left, right } => Expr::Binary {
+                op,
+                left: sub(left)?,
+                right: sub(right)?,
+            },
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr: sub(expr)?,
+                negated,
+            },
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => Expr::Like {
+                expr: sub(expr)?,
+                pattern: sub(pattern)?,
+                negated,
+            },
+            Expr::In {
+                expr,
+                list,
+                negated,
+            } => Expr::In {
+                expr: sub(expr)?,
+                list: list
+                    .into_iter()
+                    .map(|e| e.rewrite(f))
+                    .collect::<Result<_>>()?,
+                negated,
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => Expr::Between {
+                expr: sub(expr)?,
+                low: sub(low)?,
+                high: sub(high)?,
+                negated,
+            },
+            Expr::Function { name, args } => Expr::Function {
+                name,
+                args: args
+                    .into_iter()
+                    .map(|e| e.rewrite(f))
+                    .collect::<Result<_>>()?,
+            },
+            Expr::Aggregate {
+                func,
+                arg: Some(arg),
+                distinct,
+            } => Expr::Aggregate {
+                func,
+                arg: Some(sub(arg)?),
+                distinct,
+            },
+            e => e,
+        };
+        f(expr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// A `"double quoted"` or `` `backtick` `` identifier
+    Quoted(String),
+    Str(String),
+    Num(String),
+    Sym(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "||", "=", "<", ">", "+", "-", "*", "/", "%", "(", ")", ",", ".", ";",
+];
+
+const RESERVED: &[&str] = &[
+    "select", "distinct", "from", "where", "group", "by", "having", "order", "limit", "offset",
+    "join", "inner", "left", "outer", "on", "as", "and", "or", "not", "is", "null", "like", "in",
+    "between", "asc", "desc",
+];
+
+fn tokenize(sql: &str) -> Result<Vec<(Token, usize, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let token = if c == '\'' || c == '"' || c == '`' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    // a doubled quote is an escaped quote
+                    Some((_, q)) if q == c => {
+                        if chars.peek().is_some_and(|&(_, n)| n == c) {
+                            chars.next();
+                            s.push(c);
+                        } else {
+                            break;
+                        }
+                    }
+                    Some((_, ch)) => s.push(ch),
+                    None => return Err(anyhow!("Unterminated quote at position {}", start)),
+                }
+            }
+            if c == '\'' {
+                Token::Str(s)
+            } else {
+                Token::Quoted(s)
+            }
+        } else if c.is_ascii_digit() {
+            let mut s = String::new();
+            while let Some(&(_, ch)) = chars.peek() {
+                if !(ch.is_ascii_digit() || ch == '.') {
+                    break;
+                }
+                s.push(ch);
+                chars.next();
+            }
+            // an exponent like `1e3` or `2.5E-4`
+            let mut ahead = chars.clone().map(|(_, ch)| ch).peekable();
+            if let Some(e) = ahead.next_if(|ch| matches!(ch, 'e' | 'E')) {
+                let sign = ahead.next_if(|ch| matches!(ch, '+' | '-'));
+                if ahead.peek().is_some_and(char::is_ascii_digit) {
+                    s.push(e);
+                    s.extend(sign);
+                    chars.nth(sign.map_or(0, |_| 1));
+                    while let Some(&(_, ch)) = chars.peek() {
+                        if !ch.is_ascii_digit() {
+                            break;
+                        }
+                        s.push(ch);
+                        chars.next();
+                    }
+                }
+            }
+            // `1e` or `2x` is a typo, not a number with an alias
+            if let Some(&(i, ch)) = chars.peek() {
+                if ch.is_alphanumeric() || ch == '_' {
+                    return Err(anyhow!(
+                        "Unexpected character {:?} after a number at position {}",
+                        ch,
+                        i
+                    ));
+                }
+            }
+            Token::Num(s)
+        } else if c.is_alphabetic() || c == '_' {
+            let mut s = String::new();
+            while let Some(&(_, ch)) = chars.peek() {
+                if !(ch.is_alphanumeric() || ch == '_') {
+                    break;
+                }
+                s.push(ch);
+                chars.next();
+            }
+            Token::Word(s)
+        } else {
+            let rest = &sql[start..];
+            let sym = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(**s))
+                .ok_or_else(|| anyhow!("Unexpected character {:?} at position {}", c, start))?;
+            for _ in 0..sym.len() {
+                chars.next();
+            }
+            Token::Sym(sym)
+        };
+        let end = chars.peek().map_or(sql.len(), |&(i, _)| i);
+        tokens.push((token, start, end));
+    }
+    Ok(tokens)
+}
+
+/// Parses a single `SELECT` statement.
+pub fn parse_query(sql: &str) -> Result<Query> {
+    let mut parser = Parser {
+        sql,
+        tokens: tokenize(sql)?,
+        pos: 0,
+    };
+    let query = parser.query()?;
+    parser.eat_sym(";");
+    match parser.tokens.get(parser.pos) {
+        None => Ok(query),
+        Some(_) => Err(parser.error("end of query")),
+    }
+}
+
+struct Parser<'a> {
+    sql: &'a str,
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _, _)| t)
+    }
+
+    fn error(&self, expected: &str) -> anyhow::Error {
+        match self.tokens.get(self.pos) {
+            Some((_, start, end)) => anyhow!(
+                "SQL syntax error at position {}: expected {}, found {:?}",
+                start,
+                expected,
+                &self.sql[*start..*end]
+            ),
+            None => anyhow!(
+                "SQL syntax error: expected {}, found end of query",
+                expected
+            ),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(&keyword.to_uppercase()))
+        }
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Sym(s)) if *s == sym);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<()> {
+        if self.eat_sym(sym) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("{:?}", sym)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Word(w)) if !is_reserved(w) => {
+                let w = w.clone();
+                self.pos += 1;
+                Ok(w)
+            }
+            Some(Token::Quoted(q)) => {
+                let q = q.clone();
+                self.pos += 1;
+                Ok(q)
+            }
+            _ => Err(self.error("an identifier")),
+        }
+    }
+
+    /// An optional `[AS] alias` after a table or select item.
+    fn alias(&mut self) -> Result<Option<String>> {
+        if self.eat_keyword("as") {
+            return self.ident().map(Some);
+        }
+        match self.peek() {
+            Some(Token::Word(w)) if !is_reserved(w) => self.ident().map(Some),
+            Some(Token::Quoted(_)) => self.ident().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        match self.peek() {
+            Some(Token::Num(n)) => {
+                let n = n.parse().map_err(|_| self.error("a whole number"))?;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => Err(self.error("a whole number")),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query> {
+        self.expect_keyword("select")?;
+        let mut query = Query {
+            distinct: self.eat_keyword("distinct"),
+            ..Default::default()
+        };
+        loop {
+            query.items.push(self.select_item()?);
+            if !self.eat_sym(",") {
+                break;
+            }
+        }
+        if self.eat_keyword("from") {
+            query.from = Some(self.table_ref()?);
+            loop {
+                let left = if self.eat_keyword("left") {
+                    self.eat_keyword("outer");
+                    true
+                } else {
+                    self.eat_keyword("inner");
+                    false
+                };
+                if !self.eat_keyword("join") {
+                    if left {
+                        return Err(self.error("JOIN"));
+                    }
+                    break;
+                }
+                let table = self.table_ref()?;
+                self.expect_keyword("on")?;
+                let on = self.expr()?;
+                query.joins.push(Join { table, left, on });
+            }
+        }
+        if self.eat_keyword("where") {
+            query.filter = Some(self.expr()?);
+        }
+        if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            query.group_by = self.expr_list()?;
+        }
+        if self.eat_keyword("having") {
+            query.having = Some(self.expr()?);
+        }
+        if self.eat_keyword("order") {
+            self.expect_keyword("by")?;
+            loop {
+                let expr = self.expr()?;
+                let desc = if self.eat_keyword("desc") {
+                    true
+                } else {
+                    self.eat_keyword("asc");
+                    false
+                };
+                query.order_by.push((expr, desc));
+                if !self.eat_sym(",") {
+                    break;
+                }
+            }
+        }
+        if self.eat_keyword("limit") {
+            query.limit = Some(self.usize()?);
+        }
+        if self.eat_keyword("offset") {
+            query.offset = self.usize()?;
+        }
+        Ok(query)
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem> {
+        if self.eat_sym("*") {
+            return Ok(SelectItem::Wildcard(None));
+        }
+        // `table.*`
+        if let (Some(Token::Word(_) | Token::Quoted(_)), Some((Token::Sym("."), _, _))) =
+            (self.peek(), self.tokens.get(self.pos + 1))
+        {
+            if matches!(self.tokens.get(self.pos + 2), Some((Token::Sym("*"), _, _))) {
+                let table = self.ident()?;
+                self.pos += 2;
+                return Ok(SelectItem::Wildcard(Some(table)));
+            }
+        }
+        let start = self.tokens[self.pos.min(self.tokens.len() - 1)].1;
+        let expr = self.expr()?;
+        let end = self.tokens[self.pos - 1].2;
+        let name = match (self.alias()?, &expr) {
+            (Some(alias), _) => alias,
+            (None, Expr::Column { name, .. }) => name.clone(),
+            (None, _) => self.sql[start..end].to_string(),
+        };
+        Ok(SelectItem::Expr { expr, name })
+    }
+
+    fn table_ref(&mut self) -> Result<TableRef> {
+        let name = self.ident()?;
+        let alias = self.alias()?;
+        Ok(TableRef { name, alias })
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>> {
+        let mut list = vec![self.expr()?];
+        while self.eat_sym(",") {
+            list.push(self.expr()?);
+        }
+        Ok(list)
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut left = self.and()?;
+        while self.eat_keyword("or") {
+            left = binary(BinaryOp::Or, left, self.and()?);
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut left = self.not()?;
+        while self.eat_keyword("and") {
+            left = binary(BinaryOp::And, left, self.not()?);
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let left = self.additive()?;
+        let op = match self.peek() {
+            Some(Token::Sym("=")) => Some(BinaryOp::Eq),
+            Some(Token::Sym("!=" | "<>")) => Some(BinaryOp::NotEq),
+            Some(Token::Sym("<")) => Some(BinaryOp::Lt),
+            Some(Token::Sym("<=")) => Some(BinaryOp::LtEq),
+            Some(Token::Sym(">")) => Some(BinaryOp::Gt),
+            Some(Token::Sym(">=")) => Some(BinaryOp::GtEq),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.pos += 1;
+            return Ok(binary(op, left, self.additive()?));
+        }
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+        let negated = self.eat_keyword("not");
+        let expr = Box::new(left);
+        if self.eat_keyword("like") {
+            let pattern = Box::new(self.additive()?);
+            Ok(Expr::Like {
+                expr,
+                pattern,
+                negated,
+            })
+        } else if self.eat_keyword("in") {
+            self.expect_sym("(")?;
+            let list = self.expr_list()?;
+            self.expect_sym(")")?;
+            Ok(Expr::In {
+                expr,
+                list,
+                negated,
+            })
+        } else if self.eat_keyword("between") {
+            let low = Box::new(self.additive()?);
+            self.expect_keyword("and")?;
+            let high = Box::new(self.additive()?);
+            Ok(Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            })
+        } else if negated {
+            Err(self.error("LIKE, IN or BETWEEN"))
+        } else {
+            Ok(*expr)
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Sym("+")) => BinaryOp::Add,
+                Some(Token::Sym("-")) => BinaryOp::Sub,
+                Some(Token::Sym("||")) => BinaryOp::Concat,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = binary(op, left, self.multiplicative()?);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Sym("*")) => BinaryOp::Mul,
+                Some(Token::Sym("/")) => BinaryOp::Div,
+                Some(Token::Sym("%")) => BinaryOp::Mod,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = binary(op, left, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_sym("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.eat_sym("+");
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("an expression"))?;
+        match token {
+            Token::Num(n) => {
+                self.pos += 1;
+                let value = match n.parse::<i64>() {
+                    Ok(i) => Value::from(i),
+                    Err(_) => n.parse::<f64>().map(Value::from).map_err(|_| {
+                        self.pos -= 1;
+                        self.error("a number")
+                    })?,
+                };
+                Ok(Expr::Literal(value))
+            }
+            Token::Str(s) => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::String(s)))
+            }
+            Token::Sym("(") => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect_sym(")")?;
+                Ok(expr)
+            }
+            Token::Word(w) if w.eq_ignore_ascii_case("null") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Null))
+            }
+            Token::Word(w) if w.eq_ignore_ascii_case("true") || w.eq_ignore_ascii_case("false") => {
+                self.pos += 1;
+                Ok(Expr::Literal(Value::Bool(w.eq_ignore_ascii_case("true"))))
+            }
+            Token::Word(w)
+                if matches!(self.tokens.get(self.pos + 1), Some((Token::Sym("("), _, _))) =>
+            {
+                self.pos += 2;
+                self.function(&w.to_ascii_lowercase())
+            }
+            Token::Word(_) | Token::Quoted(_) => {
+                let name = self.ident()?;
+                if self.eat_sym(".") {
+                    let column = self.ident()?;
+                    return Ok(Expr::Column {
+                        table: Some(name),
+                        name: column,
+                    });
+                }
+                Ok(Expr::Column { table: None, name })
+            }
+            _ => Err(self.error("an expression")),
+        }
+    }
+
+    /// Parses the arguments of a function call, after the opening parenthesis.
+    fn function(&mut self, name: &str) -> Result<Expr> {
+        let func = match name {
+            "count" => Some(AggFunc::Count),
+            "sum" => Some(AggFunc::Sum),
+            "avg" => Some(AggFunc::Avg),
+            "min" => Some(AggFunc::Min),
+            "max" => Some(AggFunc::Max),
+            _ => None,
+        };
+        let expr = match func {
+            Some(AggFunc::Count) if self.eat_sym("*") => Expr::Aggregate {
+                func: AggFunc::Count,
+                arg: None,
+                distinct: false,
+            },
+            Some(func) => {
+                let distinct = self.eat_keyword("distinct");
+                Expr::Aggregate {
+                    func,
+                    arg: Some(Box::new(self.expr()?)),
+                    distinct,
+                }
+            }
+            None => {
+                let args = if matches!(self.peek(), Some(Token::Sym(")"))) {
+                    vec![]
+                } else {
+                    self.expr_list()?
+                };
+                Expr::Function {
+                    name: name.to_string(),
+                    args,
+                }
+            }
+        };
+        self.expect_sym(")")?;
+        Ok(expr)
+    }
+}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    RESERVED.iter().any(|k| k.eq_ignore_ascii_case(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() -> Result<()> {
+        let q = parse_query(
+            r#"SELECT Position, count(*) AS players, max("Kit Number") FROM juventus j
+               WHERE Nationality <> 'Italy' AND "Kit Number" BETWEEN 1 AND 50
+               GROUP BY Position HAVING count(*) > 1 ORDER BY players DESC, 1 LIMIT 3 OFFSET 1"#,
+        )?;
+        let names = q
+            .items
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expr { name, .. } => name.as_str(),
+                SelectItem::Wildcard(_) => "*",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Position", "players", r#"max("Kit Number")"#]);
+        let from = q.from.unwrap();
+        assert_eq!((from.name.as_str(), from.qualifier()), ("juventus", "j"));
+        assert!(q.filter.is_some() && q.having.unwrap().has_aggregate());
+        assert_eq!(q.group_by.len(), 1);
+        assert!(q.order_by[0].1);
+        assert_eq!(q.order_by[1].0, Expr::Literal(Value::from(1)));
+        assert_eq!((q.limit, q.offset), (Some(3), 1));
+
+        let q = parse_query("select p.*, c.Name from p left join c on p.Team = c.Id;")?;
+        assert!(matches!(&q.items[0], SelectItem::Wildcard(Some(t)) if t == "p"));
+        assert!(q.joins[0].left);
+
+        let q = parse_query("SELECT 1 + 2 * 3 = 7, 'it''s'")?;
+        assert!(matches!(
+            &q.items[0],
+            SelectItem::Expr {
+                expr: Expr::Binary {
+                    op: BinaryOp::Eq,
+                    ..
+                },
+                ..
+            }
+        ));
+
+        let err = parse_query("SELECT Name FROM t WHERE").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "SQL syntax error: expected an expression, found end of query"
+        );
+        let err = parse_query("SELECT Name FROM t LIMIT x").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "SQL syntax error at position 25: expected a whole number, found \"x\""
+        );
+        assert!(parse_query("SELECT 'open").is_err());
+
+        let q = parse_query("SELECT 1e3, 2.5E-1 x, 1E+2")?;
+        let values = q
+            .items
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expr {
+                    expr: Expr::Literal(v),
+                    name,
+                } => (v.as_f64(), name.as_str()),
+                _ => (None, ""),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                (Some(1000.0), "1e3"),
+                (Some(0.25), "x"),
+                (Some(100.0), "1E+2")
+            ]
+        );
+        let err = parse_query("SELECT 1e").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unexpected character 'e' after a number at position 8"
+        );
+        assert!(parse_query("SELECT 2x").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,435 @@
+use super::csv_convert::{open_csv, record_writer};
+use super::csv_show::{render_table, TableLayout};
+use super::csv_types::{infer_type, ColumnType};
+use crate::cli::{CsvReaderOpts, OutputFormat};
+use anyhow::Result;
+use csv::StringRecord;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+
+/// Distinct values are counted exactly up to this many, then estimated.
+const EXACT_DISTINCT_LIMIT: usize = 10_000;
+/// Numeric values kept for the median; it is exact for columns with fewer values.
+const MEDIAN_SAMPLE_SIZE: usize = 10_000;
+/// HyperLogLog precision, 2^14 registers for a ~0.8% standard error.
+const HLL_PRECISION: u32 = 14;
+
+/// Profile of a single CSV column.
+#[derive(Debug, Serialize)]
+pub struct ColumnStats {
+    pub column: String,
+    #[serde(rename = "type")]
+    pub ty: Option<ColumnType>,
+    pub count: u64,
+    pub nulls: u64,
+    pub distinct: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median: Option<f64>,
+    pub top: Vec<TopValue>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TopValue {
+    pub value: String,
+    pub count: u64,
+}
+
+/// Profiles every column of a CSV file in a single pass with bounded memory.
+///
+/// Distinct counts switch to a HyperLogLog estimate, medians to a reservoir sample and
+/// the most frequent values to the space-saving algorithm once a column gets large.
+pub fn process_csv_stats(
+    input: &str,
+    opts: &CsvReaderOpts,
+    top: usize,
+) -> Result<Vec<ColumnStats>> {
+    let (mut reader, headers) = open_csv(input, opts)?;
+    let mut profiles = headers
+        .iter()
+        .map(|_| ColumnProfile::new(top))
+        .collect::<Vec<_>>();
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        for (profile, cell) in profiles.iter_mut().zip(record.iter()) {
+            profile.add(cell);
+        }
+    }
+
+    Ok(headers
+        .iter()
+        .zip(profiles)
+        .map(|(name, profile)| profile.finish(name, top))
+        .collect())
+}
+
+/// Writes column stats as a text table, or in any of the conversion formats.
+pub fn write_stats(
+    stats: &[ColumnStats],
+    format: Option<OutputFormat>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let columns = [
+        "column", "type", "count", "nulls", "distinct", "min", "max", "mean", "median", "top",
+    ];
+    let Some(format) = format else {
+        let rows = stats.iter().map(table_row).collect::<Vec<_>>();
+        let layout = TableLayout {
+            max_cell_width: 60,
+            max_width: None,
+        };
+        let headers = columns.map(String::from);
+        return render_table(&headers, &rows, &layout, out);
+    };
+    let mut writer = record_writer(format, out, &StringRecord::from(columns.to_vec()));
+    for s in stats {
+        writer.write_record(&serde_json::to_value(s)?)?;
+    }
+    writer.finish()
+}
+
+fn table_row(s: &ColumnStats) -> Vec<String> {
+    let num = |v: Option<f64>| v.map(format_number).unwrap_or_default();
+    let top = s
+        .top
+        .iter()
+        .map(|t| format!("{} ({})", t.value, t.count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    vec![
+        s.column.clone(),
+        s.ty.map(|t| t.to_string()).unwrap_or_default(),
+        s.count.to_string(),
+        s.nulls.to_string(),
+        s.distinct.to_string(),
+        num(s.min),
+        num(s.max),
+        num(s.mean),
+        num(s.median),
+        top,
+    ]
+}
+
+fn format_number(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e15 {
+        format!("{}", v as i64)
+    } else {
+        let s = format!("{:.4}", v);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Running statistics for one column.
+struct ColumnProfile {
+    count: u64,
+    nulls: u64,
+    ty: Option<ColumnType>,
+    distinct: DistinctCounter,
+    numbers: NumericStats,
+    frequent: SpaceSaving,
+}
+
+impl ColumnProfile {
+    fn new(top: usize) -> Self {
+        Self {
+            count: 0,
+            nulls: 0,
+            ty: None,
+            distinct: DistinctCounter::default(),
+            numbers: NumericStats::default(),
+            // track more candidates than reported to keep the top-N accurate
+            frequent: SpaceSaving::new((top * 10).max(100)),
+        }
+    }
+
+    fn add(&mut self, cell: &str) {
+        let Some(ty) = infer_type(cell, true) else {
+            self.nulls += 1;
+            return;
+        };
+        self.count += 1;
+        self.ty = Some(self.ty.map_or(ty, |t| t.merge(ty)));
+        self.distinct.add(cell);
+        self.frequent.add(cell);
+        if ty.is_numeric() {
+            if let Ok(v) = cell.parse::<f64>() {
+                self.numbers.add(v);
+            }
+        }
+    }
+
+    fn finish(self, name: &str, top: usize) -> ColumnStats {
+        let numeric = self.ty.is_some_and(|t| t.is_numeric());
+        let numbers = self.numbers;
+        ColumnStats {
+            column: name.to_string(),
+            ty: self.ty,
+            count: self.count,
+            nulls: self.nulls,
+            distinct: self.distinct.estimate(),
+            min: numeric.then_some(numbers.min),
+            max: numeric.then_some(numbers.max),
+            mean: numeric.then(|| numbers.sum / numbers.count as f64),
+            median: if numeric { numbers.median() } else { None },
+            top: self.frequent.top(top),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NumericStats {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    sample: Vec<f64>,
+}
+
+impl Default for NumericStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sample: Vec::new(),
+        }
+    }
+}
+
+impl NumericStats {
+    fn add(&mut self, v: f64) {
+        self.count += 1;
+        self.sum += v;
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+        // reservoir sampling keeps a uniform sample of all values seen so far
+        if self.sample.len() < MEDIAN_SAMPLE_SIZE {
+            self.sample.push(v);
+        } else {
+            let i = rand::thread_rng().gen_range(0..self.count) as usize;
+            if i < MEDIAN_SAMPLE_SIZE {
+                self.sample[i] = v;
+            }
+        }
+    }
+
+    fn median(mut self) -> Option<f64> {
+        if self.sample.is_empty() {
+            return None;
+        }
+        self.sample.sort_by(f64::total_cmp);
+        let mid = self.sample.len() / 2;
+        Some(if self.sample.len().is_multiple_of(2) {
+            (self.sample[mid - 1] + self.sample[mid]) / 2.0
+        } else {
+            self.sample[mid]
+        })
+    }
+}
+
+/// Exact distinct counting that degrades to HyperLogLog for high cardinality columns.
+#[derive(Debug)]
+enum DistinctCounter {
+    Exact(HashSet<u64>),
+    Approx(Box<[u8]>),
+}
+
+impl Default for DistinctCounter {
+    fn default() -> Self {
+        DistinctCounter::Exact(HashSet::new())
+    }
+}
+
+impl DistinctCounter {
+    fn add(&mut self, value: &str) {
+        let hash = hash_value(value);
+        match self {
+            DistinctCounter::Exact(set) => {
+                set.insert(hash);
+                if set.len() > EXACT_DISTINCT_LIMIT {
+                    let mut registers = vec![0u8; 1 << HLL_PRECISION].into_boxed_slice();
+                    for h in set.iter() {
+                        hll_add(&mut registers, *h);
+                    }
+                    *self = DistinctCounter::Approx(registers);
+                }
+            }
+            DistinctCounter::Approx(registers) => hll_add(registers, hash),
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        match self {
+            DistinctCounter::Exact(set) => set.len() as u64,
+            DistinctCounter::Approx(registers) => hll_estimate(registers),
+        }
+    }
+}
+
+fn hash_value(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hll_add(registers: &mut [u8], hash: u64) {
+    let index = (hash >> (64 - HLL_PRECISION)) as usize;
+    let rest = hash << HLL_PRECISION;
+    let rank = (rest.leading_zeros() + 1).min(64 - HLL_PRECISION + 1) as u8;
+    registers[index] = registers[index].max(rank);
+}
+
+fn hll_estimate(registers: &[u8]) -> u64 {
+    let m = registers.len() as f64;
+    let alpha = 0.7213 / (1.0 + 1.079 / m);
+    let sum = registers
+        .iter()
+        .map(|r| 2f64.powi(-(*r as i32)))
+        .sum::<f64>();
+    let estimate = alpha * m * m / sum;
+    let zeros = registers.iter().filter(|r| **r == 0).count();
+    if estimate <= 2.5 * m && zeros > 0 {
+        // linear counting is more accurate for small cardinalities
+        (m * (m / zeros as f64).ln()).round() as u64
+    } else {
+        estimate.round() as u64
+    }
+}
+
+/// Space-saving heavy hitters: exact while there are fewer distinct values than `capacity`.
+#[derive(Debug)]
+struct SpaceSaving {
+    capacity: usize,
+    counts: HashMap<String, u64>,
+}
+
+impl SpaceSaving {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counts: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, value: &str) {
+        if let Some(count) = self.counts.get_mut(value) {
+            *count += 1;
+            return;
+        }
+        if self.counts.len() < self.capacity {
+            self.counts.insert(value.to_string(), 1);
+            return;
+        }
+        // replace the least frequent candidate, inheriting its count
+        let (min_value, min_count) = self
+            .counts
+            .iter()
+            .min_by_key(|(_, c)| **c)
+            .map(|(v, c)| (v.clone(), *c))
+            .expect("capacity is never zero");
+        self.counts.remove(&min_value);
+        self.counts.insert(value.to_string(), min_count + 1);
+    }
+
+    fn top(self, n: usize) -> Vec<TopValue> {
+        let mut counts = self.counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+        counts
+            .into_iter()
+            .take(n)
+            .map(|(value, count)| TopValue { value, count })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_stats() -> Result<()> {
+        let opts = CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        };
+        let stats = process_csv_stats("assets/juventus.csv", &opts, 2)?;
+        assert_eq!(stats.len(), 5);
+
+        let nationality = &stats[3];
+        assert_eq!(nationality.ty, Some(ColumnType::String));
+        assert_eq!((nationality.count, nationality.nulls), (27, 0));
+        assert_eq!(nationality.top[0].value, "Italy");
+        assert!(nationality.min.is_none());
+
+        let kit = &stats[4];
+        assert_eq!(kit.ty, Some(ColumnType::Integer));
+        assert_eq!(kit.distinct, 27);
+        assert_eq!((kit.min, kit.max), (Some(1.0), Some(77.0)));
+        assert!(kit.mean.is_some() && kit.median.is_some());
+
+        let mut buf = Vec::new();
+        write_stats(&stats, None, &mut buf)?;
+        let table = String::from_utf8(buf)?;
+        assert!(table.starts_with("column      | type    | count |"));
+
+        let mut buf = Vec::new();
+        write_stats(&stats, Some(OutputFormat::Json), &mut buf)?;
+        let json: serde_json::Value = serde_json::from_slice(&buf)?;
+        assert_eq!(json[4]["type"], "integer");
+        assert_eq!(json[4]["max"], 77.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_distinct_counter() {
+        let mut counter = DistinctCounter::default();
+        for i in 0..100_000 {
+            counter.add(&(i % 50_000).to_string());
+        }
+        assert!(matches!(counter, DistinctCounter::Approx(_)));
+        let estimate = counter.estimate() as f64;
+        assert!(
+            (estimate - 50_000.0).abs() / 50_000.0 < 0.05,
+            "{}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn test_numeric_stats() {
+        let mut stats = NumericStats::default();
+        for v in [3.0, 1.0, 4.0, 1.5] {
+            stats.add(v);
+        }
+        assert_eq!((stats.min, stats.max, stats.sum), (1.0, 4.0, 9.5));
+        assert_eq!(stats.median(), Some(2.25));
+    }
+
+    #[test]
+    fn test_space_saving() {
+        let mut top = SpaceSaving::new(2);
+        for v in ["a", "b", "a", "c", "a"] {
+            top.add(v);
+        }
+        let ret = top.top(1);
+        assert_eq!(
+            ret,
+            vec![TopValue {
+                value: "a".into(),
+                count: 3
+            }]
+        );
+    }
+}
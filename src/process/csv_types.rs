@@ -0,0 +1,268 @@
+use crate::cli::CsvTypeOpts;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The type of a CSV column, either pinned in a schema file or inferred from the data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+}
+
+/// Per-column type pins, loaded from a YAML (or JSON) map of column name to type.
+pub type Schema = HashMap<String, ColumnType>;
+
+pub fn load_schema(path: &str) -> Result<Schema> {
+    let content = std::fs::read_to_string(path)?;
+    serde_yaml::from_str(&content).with_context(|| format!("Invalid schema file {}", path))
+}
+
+#[derive(Debug, Copy, Clone)]
+enum CellTyping {
+    Raw,
+    Infer { dates: bool },
+    Pinned(ColumnType),
+}
+
+/// Turns the raw string cells of a record into typed JSON values.
+#[derive(Debug)]
+pub struct CellTyper {
+    columns: Vec<CellTyping>,
+    headers: StringRecord,
+}
+
+impl CellTyper {
+    pub fn new(headers: &StringRecord, opts: &CsvTypeOpts) -> Result<Self> {
+        let mut schema = match &opts.schema {
+            Some(path) => load_schema(path)?,
+            None => Schema::new(),
+        };
+        let fallback = if opts.infer_types {
+            CellTyping::Infer {
+                dates: opts.infer_dates,
+            }
+        } else {
+            CellTyping::Raw
+        };
+        let columns = headers
+            .iter()
+            .map(|name| schema.remove(name).map_or(fallback, CellTyping::Pinned))
+            .collect();
+        if let Some(name) = schema.keys().next() {
+            return Err(anyhow!("Schema column {:?} not found in input", name));
+        }
+        Ok(Self {
+            columns,
+            headers: headers.clone(),
+        })
+    }
+
+    /// Converts a record; `row` is the 1-based data row number used in error messages.
+    pub fn convert(&self, row: u64, record: &StringRecord) -> Result<Vec<Value>> {
+        record
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match self.columns.get(i) {
+                None | Some(CellTyping::Raw) => Ok(Value::from(cell)),
+                Some(CellTyping::Infer { dates }) => Ok(infer_value(cell, *dates)),
+                Some(CellTyping::Pinned(ty)) => parse_value(cell, *ty).ok_or_else(|| {
+                    anyhow!(
+                        "row {}, column {} ({}): expected {}, got {:?}",
+                        row,
+                        i + 1,
+                        &self.headers[i],
+                        ty,
+                        cell
+                    )
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Guesses the most specific type for a cell. Empty cells become null.
+pub fn infer_value(cell: &str, dates: bool) -> Value {
+    match infer_type(cell, dates) {
+        Some(ty) => parse_value(cell, ty).unwrap_or_else(|| Value::from(cell)),
+        None => Value::Null,
+    }
+}
+
+/// Guesses the most specific type for a cell, `None` for empty cells.
+pub fn infer_type(cell: &str, dates: bool) -> Option<ColumnType> {
+    if cell.is_empty() {
+        return None;
+    }
+    // keep values like zip codes "00501" as strings so leading zeros survive
+    if has_leading_zero(cell) {
+        return Some(ColumnType::String);
+    }
+    let types: &[ColumnType] = if dates {
+        &[
+            ColumnType::Boolean,
+            ColumnType::Integer,
+            ColumnType::Float,
+            ColumnType::Date,
+        ]
+    } else {
+        &[ColumnType::Boolean, ColumnType::Integer, ColumnType::Float]
+    };
+    let ty = types
+        .iter()
+        .find(|ty| parse_value(cell, **ty).is_some())
+        .copied();
+    Some(ty.unwrap_or(ColumnType::String))
+}
+
+impl ColumnType {
+    /// The narrowest type that fits values of both types, e.g. integer and float is float.
+    pub fn merge(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, ColumnType::Integer | ColumnType::Float)
+    }
+}
+
+/// Parses a cell as the given type, `None` if it doesn't match. Empty cells are null.
+pub fn parse_value(cell: &str, ty: ColumnType) -> Option<Value> {
+    if cell.is_empty() {
+        return Some(Value::Null);
+    }
+    match ty {
+        ColumnType::String => Some(Value::from(cell)),
+        ColumnType::Integer => cell.parse::<i64>().ok().map(Value::from),
+        ColumnType::Float => parse_float(cell).map(Value::Number),
+        ColumnType::Boolean => match cell.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ColumnType::Date => parse_date(cell).map(Value::String),
+    }
+}
+
+fn parse_float(cell: &str) -> Option<Number> {
+    // reject "inf", "NaN" and friends which `f64::from_str` happily accepts
+    if !cell
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'-' | b'+' | b'e' | b'E'))
+    {
+        return None;
+    }
+    cell.parse::<f64>().ok().and_then(Number::from_f64)
+}
+
+/// Normalizes an ISO 8601 date or date-time to its canonical string form.
+fn parse_date(cell: &str) -> Option<String> {
+    if let Ok(d) = NaiveDate::parse_from_str(cell, "%Y-%m-%d") {
+        return Some(d.format("%Y-%m-%d").to_string());
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(cell) {
+        return Some(dt.to_rfc3339());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(cell, fmt).ok())
+        .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+}
+
+fn has_leading_zero(cell: &str) -> bool {
+    let digits = cell.strip_prefix('-').unwrap_or(cell).as_bytes();
+    digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit()
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ColumnType::String => "string",
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_value() {
+        assert_eq!(infer_value("", false), Value::Null);
+        assert_eq!(infer_value("42", false), json!(42));
+        assert_eq!(infer_value("-1.5", false), json!(-1.5));
+        assert_eq!(infer_value("TRUE", false), json!(true));
+        assert_eq!(infer_value("00501", false), json!("00501"));
+        assert_eq!(infer_value("inf", false), json!("inf"));
+        assert_eq!(infer_value("Italy", false), json!("Italy"));
+        assert_eq!(infer_value("1990-04-18", false), json!("1990-04-18"));
+        assert_eq!(infer_value("1990-04-18", true), json!("1990-04-18"));
+        assert_eq!(infer_value("1990-02-30", true), json!("1990-02-30"));
+        assert_eq!(
+            infer_value("2019-05-01 10:30:00", true),
+            json!("2019-05-01T10:30:00")
+        );
+        assert_eq!(parse_value("1990-02-30", ColumnType::Date), None);
+    }
+
+    #[test]
+    fn test_merge_types() {
+        assert_eq!(infer_type("", true), None);
+        let ty = infer_type("1", false).unwrap();
+        assert_eq!(ty, ColumnType::Integer);
+        assert_eq!(ty.merge(ColumnType::Float), ColumnType::Float);
+        assert_eq!(ty.merge(ColumnType::Boolean), ColumnType::String);
+        assert_eq!(ColumnType::Date.merge(ColumnType::Date), ColumnType::Date);
+    }
+
+    #[test]
+    fn test_cell_typer_schema() -> Result<()> {
+        let headers = StringRecord::from(vec!["Name", "Kit Number", "Captain"]);
+        let dir = tempfile::tempdir()?;
+        let schema = dir.path().join("schema.yaml");
+        std::fs::write(&schema, "Kit Number: integer\nCaptain: boolean\n")?;
+        let opts = CsvTypeOpts {
+            infer_types: false,
+            infer_dates: false,
+            schema: Some(schema.to_string_lossy().into_owned()),
+        };
+        let typer = CellTyper::new(&headers, &opts)?;
+
+        let record = StringRecord::from(vec!["Buffon", "77", "true"]);
+        assert_eq!(
+            typer.convert(1, &record)?,
+            vec![json!("Buffon"), json!(77), json!(true)]
+        );
+
+        let record = StringRecord::from(vec!["Dybala", "ten", ""]);
+        let err = typer.convert(2, &record).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"row 2, column 2 (Kit Number): expected integer, got "ten""#
+        );
+
+        std::fs::write(&schema, "Number: integer\n")?;
+        assert!(CellTyper::new(&headers, &opts).is_err());
+        Ok(())
+    }
+}
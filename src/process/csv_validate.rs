@@ -0,0 +1,313 @@
+use super::csv_convert::open_csv;
+use super::csv_types::{parse_value, ColumnType};
+use crate::cli::{CsvReaderOpts, OutputFormat};
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+/// Rules for a column in a validation schema: a bare type like `integer`, or a map.
+///
+/// ```yaml
+/// Name: { required: true, unique: true }
+/// Kit Number: { type: integer, min: 1, max: 99 }
+/// Position: { enum: [Goalkeeper, Defender, Midfielder, Forward] }
+/// Nationality: { pattern: "^[A-Z]" }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ColumnSpec {
+    Type(ColumnType),
+    Rules(ColumnRules),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnRules {
+    #[serde(rename = "type")]
+    ty: Option<ColumnType>,
+    /// The column must exist and none of its cells may be empty
+    #[serde(default)]
+    required: bool,
+    #[serde(default)]
+    unique: bool,
+    pattern: Option<String>,
+    #[serde(rename = "enum")]
+    values: Option<Vec<String>>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+/// A single failed check; `row` and `column` are 1-based and absent for missing columns.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Violation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    pub name: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub rows: u64,
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+struct ColumnCheck {
+    index: usize,
+    name: String,
+    rules: ColumnRules,
+    pattern: Option<Regex>,
+    seen: HashSet<String>,
+}
+
+impl ColumnCheck {
+    /// Returns a message for every rule the cell breaks.
+    fn check(&mut self, cell: &str) -> Vec<String> {
+        let rules = &self.rules;
+        if cell.is_empty() {
+            return if rules.required {
+                vec!["required value is empty".to_string()]
+            } else {
+                vec![]
+            };
+        }
+        let mut failed = Vec::new();
+        if let Some(ty) = rules.ty {
+            if parse_value(cell, ty).is_none() {
+                failed.push(format!("expected {}, got {:?}", ty, cell));
+            }
+        }
+        if let Some(values) = &rules.values {
+            if !values.iter().any(|v| v == cell) {
+                failed.push(format!("{:?} is not one of {}", cell, values.join(", ")));
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(cell) {
+                failed.push(format!("{:?} does not match {}", cell, pattern));
+            }
+        }
+        if rules.min.is_some() || rules.max.is_some() {
+            // `f64::from_str` accepts "NaN" and "inf", which no range should let through
+            match cell.parse::<f64>().ok().filter(|n| n.is_finite()) {
+                None => failed.push(format!("expected a number, got {:?}", cell)),
+                Some(n)
+                    if rules.min.is_some_and(|min| n < min)
+                        || rules.max.is_some_and(|max| n > max) =>
+                {
+                    let bound = |b: Option<f64>| b.map_or("..".to_string(), |b| b.to_string());
+                    failed.push(format!(
+                        "{} is out of range [{}, {}]",
+                        cell,
+                        bound(rules.min),
+                        bound(rules.max)
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        if rules.unique && !self.seen.insert(cell.to_string()) {
+            failed.push(format!("duplicate value {:?}", cell));
+        }
+        failed
+    }
+}
+
+/// Checks a CSV file against a validation schema, collecting every violation.
+///
+/// Columns not mentioned in the schema are not checked. A schema of bare types is also a
+/// valid `--schema` file for `csv` conversion.
+pub fn process_csv_validate(
+    input: &str,
+    schema: &str,
+    opts: &CsvReaderOpts,
+) -> Result<ValidationReport> {
+    let content = std::fs::read_to_string(schema)?;
+    let specs: BTreeMap<String, ColumnSpec> = serde_yaml::from_str(&content)
+        .with_context(|| format!("Invalid schema file {}", schema))?;
+
+    let (mut reader, headers) = open_csv(input, opts)?;
+    let mut report = ValidationReport::default();
+    let mut checks = Vec::new();
+    for (name, spec) in specs {
+        let rules = match spec {
+            ColumnSpec::Type(ty) => ColumnRules {
+                ty: Some(ty),
+                ..Default::default()
+            },
+            ColumnSpec::Rules(rules) => rules,
+        };
+        let pattern = match &rules.pattern {
+            Some(p) => Some(Regex::new(p).map_err(|e| anyhow!("Column {:?}: {}", name, e))?),
+            None => None,
+        };
+        match headers.iter().position(|h| h == name) {
+            Some(index) => checks.push(ColumnCheck {
+                index,
+                name,
+                rules,
+                pattern,
+                seen: HashSet::new(),
+            }),
+            None if rules.required => report.violations.push(Violation {
+                row: None,
+                column: None,
+                message: "required column is missing".to_string(),
+                name,
+            }),
+            None => {}
+        }
+    }
+    checks.sort_by_key(|c| c.index);
+
+    for (row, result) in reader.records().enumerate() {
+        let record = result?;
+        let row = row as u64 + 1;
+        report.rows = row;
+        for check in &mut checks {
+            let cell = record.get(check.index).unwrap_or_default();
+            for message in check.check(cell) {
+                report.violations.push(Violation {
+                    row: Some(row),
+                    column: Some(check.index + 1),
+                    name: check.name.clone(),
+                    message,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Writes a validation report as text, or as JSON/YAML for automation.
+pub fn write_validation(
+    report: &ValidationReport,
+    format: Option<OutputFormat>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        None => {
+            for v in &report.violations {
+                match (v.row, v.column) {
+                    (Some(row), Some(column)) => writeln!(
+                        out,
+                        "row {}, column {} ({}): {}",
+                        row, column, v.name, v.message
+                    )?,
+                    _ => writeln!(out, "column {}: {}", v.name, v.message)?,
+                }
+            }
+            writeln!(
+                out,
+                "{} violations in {} rows",
+                report.violations.len(),
+                report.rows
+            )?;
+        }
+        Some(OutputFormat::Json) => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        Some(OutputFormat::Yaml) => serde_yaml::to_writer(&mut *out, report)?,
+        Some(f) => {
+            return Err(anyhow!(
+                "Cannot write a validation report as {}, use json or yaml",
+                f
+            ))
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_process_csv_validate() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let input = dir.path().join("players.csv");
+        let schema = dir.path().join("schema.yaml");
+        fs::write(
+            &input,
+            "Name,Kit,Position\nBuffon,77,Goalkeeper\nDybala,ten,Forward\nBuffon,100,Coach\n,1,Defender\n",
+        )?;
+        fs::write(
+            &schema,
+            "Name: { required: true, unique: true, pattern: '^[A-Z]' }\n\
+             Kit: { type: integer, min: 1, max: 99 }\n\
+             Position: { enum: [Goalkeeper, Defender, Forward] }\n\
+             Team: { required: true }\n\
+             Age: integer\n",
+        )?;
+        let opts = CsvReaderOpts {
+            delimiter: b',',
+            header: true,
+            encoding: None,
+        };
+        let report =
+            process_csv_validate(input.to_str().unwrap(), schema.to_str().unwrap(), &opts)?;
+        assert!(!report.is_valid());
+        assert_eq!(report.rows, 4);
+
+        let mut buf = Vec::new();
+        write_validation(&report, None, &mut buf)?;
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "column Team: required column is missing\n\
+             row 2, column 2 (Kit): expected integer, got \"ten\"\n\
+             row 2, column 2 (Kit): expected a number, got \"ten\"\n\
+             row 3, column 1 (Name): duplicate value \"Buffon\"\n\
+             row 3, column 2 (Kit): 100 is out of range [1, 99]\n\
+             row 3, column 3 (Position): \"Coach\" is not one of Goalkeeper, Defender, Forward\n\
+             row 4, column 1 (Name): required value is empty\n\
+             7 violations in 4 rows\n"
+        );
+
+        // every rule a cell breaks is reported, and failing cells still count as seen
+        fs::write(
+            &schema,
+            "Code: { unique: true, pattern: '^[a-z]+$', enum: [abc, def] }\n\
+             Score: { min: 0, max: 10 }\n",
+        )?;
+        fs::write(&input, "Code,Score\nX1,NaN\nX1,inf\nabc,5\n")?;
+        let report =
+            process_csv_validate(input.to_str().unwrap(), schema.to_str().unwrap(), &opts)?;
+        let messages = report
+            .violations
+            .iter()
+            .map(|v| (v.row.unwrap(), v.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                (1, "\"X1\" is not one of abc, def"),
+                (1, "\"X1\" does not match ^[a-z]+$"),
+                (1, "expected a number, got \"NaN\""),
+                (2, "\"X1\" is not one of abc, def"),
+                (2, "\"X1\" does not match ^[a-z]+$"),
+                (2, "duplicate value \"X1\""),
+                (2, "expected a number, got \"inf\""),
+            ]
+        );
+
+        fs::write(&schema, "Kit: { type: integer, min: 1 }\n")?;
+        fs::write(&input, "Name,Kit\nBuffon,77\n")?;
+        let report =
+            process_csv_validate(input.to_str().unwrap(), schema.to_str().unwrap(), &opts)?;
+        assert!(report.is_valid());
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use crate::SecretEncoding;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// The most random bytes generated at once.
+pub const MAX_BYTES: usize = 4096;
+
+/// Generates `bytes` random bytes straight from the operating system and encodes them.
+pub fn process_genbytes(bytes: usize, encoding: SecretEncoding) -> Result<String> {
+    if !(1..=MAX_BYTES).contains(&bytes) {
+        return Err(anyhow!(
+            "Byte count {} is out of range, it must be between 1 and {}",
+            bytes,
+            MAX_BYTES
+        ));
+    }
+    let mut buf = vec![0; bytes];
+    OsRng.try_fill_bytes(&mut buf)?;
+    Ok(match encoding {
+        SecretEncoding::Hex => hex::encode(&buf),
+        SecretEncoding::Base64 => STANDARD.encode(&buf),
+        SecretEncoding::Base32 => base32::encode(base32::Alphabet::Rfc4648 { padding: true }, &buf),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_genbytes() -> Result<()> {
+        let secret = process_genbytes(32, SecretEncoding::Hex)?;
+        assert_eq!(hex::decode(&secret)?.len(), 32);
+        assert_ne!(secret, process_genbytes(32, SecretEncoding::Hex)?);
+        assert_eq!(
+            STANDARD
+                .decode(process_genbytes(512, SecretEncoding::Base64)?)?
+                .len(),
+            512
+        );
+        let secret = process_genbytes(20, SecretEncoding::Base32)?;
+        assert_eq!(secret.len(), 32);
+        assert!(secret
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || ('2'..='7').contains(&c)));
+        assert!(process_genbytes(0, SecretEncoding::Hex).is_err());
+        assert!(process_genbytes(MAX_BYTES + 1, SecretEncoding::Hex).is_err());
+        Ok(())
+    }
+}
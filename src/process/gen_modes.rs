@@ -0,0 +1,402 @@
+use super::gen_pass::{PassConfig, NUMBER, SYMBOL};
+use anyhow::{anyhow, Result};
+use rand::prelude::SliceRandom;
+use rand::Rng;
+
+const CONSONANTS: &str = "bcdfghjkmnprstvwz";
+const VOWELS: &str = "aeiou";
+const MIN_PIN_LEN: usize = 4;
+const MAX_ATTEMPTS: usize = 1000;
+
+/// The pools a pronounceable password is built from.
+struct Syllables {
+    consonants: Vec<char>,
+    vowels: Vec<char>,
+    /// Letters are mixed case, else all in the case of the pools
+    mixed: bool,
+    /// The non-letter classes, each with the number of characters required from it
+    others: [(Vec<char>, usize); 2],
+    letters: usize,
+    min_upper: usize,
+    min_lower: usize,
+}
+
+impl Syllables {
+    fn new(config: &PassConfig) -> Result<Self> {
+        if config.charset.is_some() {
+            return Err(anyhow!("An explicit charset cannot be pronounceable"));
+        }
+        let keep = |c: &char| !config.exclude.contains(*c) && !config.ambiguous.contains(*c);
+        let pool = |chars: &str, upper: bool| {
+            chars
+                .chars()
+                .map(|c| if upper { c.to_ascii_uppercase() } else { c })
+                .filter(keep)
+                .collect::<Vec<_>>()
+        };
+        let (consonants, vowels) = match (config.upper, config.lower) {
+            (true, false) => (pool(CONSONANTS, true), pool(VOWELS, true)),
+            (_, true) => (pool(CONSONANTS, false), pool(VOWELS, false)),
+            (false, false) => return Err(anyhow!("Pronounceable passwords need letters")),
+        };
+        if consonants.is_empty() || vowels.is_empty() {
+            return Err(anyhow!(
+                "No consonants or vowels left to build syllables from"
+            ));
+        }
+        let cases = [
+            (config.upper, config.min_upper, "uppercase"),
+            (config.lower, config.min_lower, "lowercase"),
+        ];
+        if let Some((_, min, name)) = cases.iter().find(|(enabled, min, _)| !enabled && *min > 0) {
+            return Err(anyhow!(
+                "At least {} {} letters required, but they are disabled",
+                min,
+                name
+            ));
+        }
+        let class = |enabled: bool, chars: &str, min: usize, name: &str| -> Result<_> {
+            match (enabled, min) {
+                (false, 0) => Ok((Vec::new(), 0)),
+                (false, _) => Err(anyhow!(
+                    "At least {} {} required, but they are disabled",
+                    min,
+                    name
+                )),
+                (true, _) => {
+                    let chars = pool(chars, false);
+                    if chars.is_empty() {
+                        return Err(anyhow!("No {} left to satisfy the minimum", name));
+                    }
+                    Ok((chars, min.max(1)))
+                }
+            }
+        };
+        let symbols = config.symbols.as_deref().unwrap_or(SYMBOL);
+        let others = [
+            class(config.number, NUMBER, config.min_digits, "digits")?,
+            class(config.symbol, symbols, config.min_symbols, "symbols")?,
+        ];
+        let (n_digits, n_symbols) = (others[0].1, others[1].1);
+
+        let mixed = config.upper && config.lower;
+        let (min_upper, min_lower) = match (config.upper, config.lower) {
+            (true, true) => (config.min_upper.max(1), config.min_lower.max(1)),
+            _ => (0, 0),
+        };
+        // in a single case, every letter counts towards its minimum
+        let min_letters = (min_upper + min_lower).max(config.min_upper + config.min_lower);
+        let required = n_digits + n_symbols + min_letters.max(2);
+        if required > config.length {
+            return Err(anyhow!(
+                "Length {} is too short for pronounceable syllables and the {} required characters",
+                config.length,
+                required
+            ));
+        }
+        Ok(Self {
+            letters: config.length - n_digits - n_symbols,
+            consonants,
+            vowels,
+            mixed,
+            others,
+            min_upper,
+            min_lower,
+        })
+    }
+}
+
+/// Generates consonant-vowel(-consonant) syllables, capitalizing some of them, with the
+/// required digits and symbols between syllables.
+pub(super) fn generate_pronounceable<R: Rng + ?Sized>(
+    config: &PassConfig,
+    rng: &mut R,
+) -> Result<String> {
+    let s = Syllables::new(config)?;
+    let mut syllables: Vec<Vec<char>> = Vec::new();
+    let mut n = 0;
+    while n < s.letters {
+        let mut syllable = vec![
+            *s.consonants.choose(rng).expect("checked in new()"),
+            *s.vowels.choose(rng).expect("checked in new()"),
+        ];
+        if rng.gen_bool(0.5) {
+            syllable.push(*s.consonants.choose(rng).expect("checked in new()"));
+        }
+        syllable.truncate(s.letters - n);
+        n += syllable.len();
+        syllables.push(syllable);
+    }
+
+    if s.mixed {
+        let can_upper = |c: char| {
+            let upper = c.to_ascii_uppercase();
+            !config.exclude.contains(upper) && !config.ambiguous.contains(upper)
+        };
+        for syllable in &mut syllables {
+            if rng.gen_bool(0.5) && can_upper(syllable[0]) {
+                syllable[0] = syllable[0].to_ascii_uppercase();
+            }
+        }
+        // top up the case minimums, syllable starts first so it stays readable
+        let positions = |syllables: &[Vec<char>], upper: bool| {
+            let mut starts = Vec::new();
+            let mut rest = Vec::new();
+            for (i, syllable) in syllables.iter().enumerate() {
+                for (j, c) in syllable.iter().enumerate() {
+                    if c.is_ascii_uppercase() != upper && (!upper || can_upper(*c)) {
+                        if j == 0 {
+                            starts.push((i, j))
+                        } else {
+                            rest.push((i, j))
+                        }
+                    }
+                }
+            }
+            (starts, rest)
+        };
+        for upper in [true, false] {
+            let min = if upper { s.min_upper } else { s.min_lower };
+            let count = syllables
+                .iter()
+                .flatten()
+                .filter(|c| c.is_ascii_uppercase() == upper)
+                .count();
+            if count >= min {
+                continue;
+            }
+            let (mut starts, mut rest) = positions(&syllables, upper);
+            starts.shuffle(rng);
+            rest.shuffle(rng);
+            let flips = starts
+                .into_iter()
+                .chain(rest)
+                .take(min - count)
+                .collect::<Vec<_>>();
+            if flips.len() < min - count {
+                return Err(anyhow!(
+                    "Not enough letters left for {} {} letters",
+                    min,
+                    if upper { "uppercase" } else { "lowercase" }
+                ));
+            }
+            for (i, j) in flips {
+                let c = &mut syllables[i][j];
+                *c = if upper {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                };
+            }
+        }
+    }
+
+    for (pool, count) in &s.others {
+        for _ in 0..*count {
+            let at = rng.gen_range(0..=syllables.len());
+            syllables.insert(at, vec![*pool.choose(rng).expect("checked in new()")]);
+        }
+    }
+    Ok(syllables.into_iter().flatten().collect())
+}
+
+/// A lower bound of the entropy in bits, ignoring syllable shapes and digit positions.
+pub(super) fn pronounceable_entropy(config: &PassConfig) -> Result<f64> {
+    let s = Syllables::new(config)?;
+    let bits = |n: usize| (n as f64).log2();
+    let mut entropy = s.letters.div_ceil(2) as f64 * bits(s.consonants.len())
+        + (s.letters / 2) as f64 * bits(s.vowels.len());
+    if s.mixed {
+        entropy += s.letters.div_ceil(3) as f64;
+    }
+    for (pool, count) in s.others.iter().filter(|(_, count)| *count > 0) {
+        entropy += *count as f64 * bits(pool.len());
+    }
+    Ok(entropy)
+}
+
+fn pin_digits(config: &PassConfig) -> Result<Vec<char>> {
+    if config.charset.is_some() {
+        return Err(anyhow!("A PIN cannot use an explicit charset"));
+    }
+    if !config.number {
+        return Err(anyhow!("A PIN needs digits, but they are disabled"));
+    }
+    if config.min_upper + config.min_lower + config.min_symbols > 0 {
+        return Err(anyhow!("A PIN only has digits"));
+    }
+    if config.length < MIN_PIN_LEN {
+        return Err(anyhow!("A PIN needs at least {} digits", MIN_PIN_LEN));
+    }
+    // 0 is only ambiguous next to letters
+    let digits = NUMBER
+        .chars()
+        .filter(|c| !config.exclude.contains(*c))
+        .collect::<Vec<_>>();
+    if digits.len() < 2 {
+        return Err(anyhow!("A PIN needs at least 2 digits to choose from"));
+    }
+    Ok(digits)
+}
+
+/// Generates a numeric PIN, regenerating trivial ones like `1234`, `9999` or `1212`.
+pub(super) fn generate_pin<R: Rng + ?Sized>(config: &PassConfig, rng: &mut R) -> Result<String> {
+    let digits = pin_digits(config)?;
+    for _ in 0..MAX_ATTEMPTS {
+        let pin = (0..config.length)
+            .map(|_| *digits.choose(rng).expect("checked in pin_digits()"))
+            .collect::<Vec<_>>();
+        if !is_trivial_pin(&pin) {
+            return Ok(pin.into_iter().collect());
+        }
+    }
+    Err(anyhow!(
+        "Every PIN generated from the allowed digits is trivial"
+    ))
+}
+
+pub(super) fn pin_entropy(config: &PassConfig) -> Result<f64> {
+    Ok(config.length as f64 * (pin_digits(config)?.len() as f64).log2())
+}
+
+/// Repeated digits, runs up or down, and repeated blocks.
+fn is_trivial_pin(pin: &[char]) -> bool {
+    let digits = pin
+        .iter()
+        .map(|c| c.to_digit(10).unwrap_or_default() as i32)
+        .collect::<Vec<_>>();
+    let step = digits[1] - digits[0];
+    if step.abs() <= 1 && digits.windows(2).all(|w| w[1] - w[0] == step) {
+        return true;
+    }
+    (2..=digits.len() / 2)
+        .filter(|period| digits.len() % period == 0)
+        .any(|period| digits.chunks(period).all(|c| c == &digits[..period]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_generate_pronounceable() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(3);
+        let config = PassConfig {
+            length: 20,
+            min_upper: 3,
+            min_digits: 2,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let pass = generate_pronounceable(&config, &mut rng)?;
+            assert_eq!(pass.chars().count(), 20);
+            assert!(pass.chars().filter(char::is_ascii_uppercase).count() >= 3);
+            assert!(pass.chars().filter(char::is_ascii_lowercase).count() >= 1);
+            assert!(pass.chars().filter(char::is_ascii_digit).count() == 2);
+            assert!(!pass.contains(|c| AMBIGUOUS_LETTERS.contains(c)));
+        }
+        // 7 consonants, 7 vowels, 5 syllable capitals, a digit and a symbol
+        assert_eq!(pronounceable_entropy(&PassConfig::default())?.round(), 56.0);
+        let no_symbols = PassConfig {
+            symbol: false,
+            ..Default::default()
+        };
+        assert!(pronounceable_entropy(&no_symbols)?.is_finite());
+
+        let short = PassConfig {
+            length: 3,
+            ..Default::default()
+        };
+        assert!(generate_pronounceable(&short, &mut rng).is_err());
+
+        let upper_only = PassConfig {
+            lower: false,
+            min_upper: 5,
+            ..Default::default()
+        };
+        let pass = generate_pronounceable(&upper_only, &mut rng)?;
+        assert!(pass.chars().filter(char::is_ascii_uppercase).count() >= 5);
+        for (upper, lower, message) in [
+            (
+                false,
+                true,
+                "At least 3 uppercase letters required, but they are disabled",
+            ),
+            (
+                true,
+                false,
+                "At least 3 lowercase letters required, but they are disabled",
+            ),
+        ] {
+            let config = PassConfig {
+                upper,
+                lower,
+                min_upper: 3,
+                min_lower: 3,
+                ..Default::default()
+            };
+            assert_eq!(
+                generate_pronounceable(&config, &mut rng)
+                    .unwrap_err()
+                    .to_string(),
+                message
+            );
+        }
+        Ok(())
+    }
+
+    const AMBIGUOUS_LETTERS: &str = "IOl";
+
+    #[test]
+    fn test_generate_pin() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(5);
+        let config = PassConfig {
+            length: 6,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let pin = generate_pin(&config, &mut rng)?;
+            assert_eq!(pin.len(), 6);
+            assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        }
+        for trivial in ["1234", "9876", "0000", "1212", "123123"] {
+            assert!(
+                is_trivial_pin(&trivial.chars().collect::<Vec<_>>()),
+                "{}",
+                trivial
+            );
+        }
+        for fine in ["1235", "1231", "9075", "112233"] {
+            assert!(
+                !is_trivial_pin(&fine.chars().collect::<Vec<_>>()),
+                "{}",
+                fine
+            );
+        }
+        let two = PassConfig {
+            length: 4,
+            exclude: "23456789".into(),
+            ..Default::default()
+        };
+        assert!(generate_pin(&two, &mut rng)?.contains('0'));
+        let short = PassConfig {
+            length: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_pin(&short, &mut rng).unwrap_err().to_string(),
+            "A PIN needs at least 4 digits"
+        );
+        let no_digits = PassConfig {
+            number: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            generate_pin(&no_digits, &mut rng).unwrap_err().to_string(),
+            "A PIN needs digits, but they are disabled"
+        );
+        Ok(())
+    }
+}
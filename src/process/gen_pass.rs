@@ -1,52 +1,289 @@
+use super::gen_modes::{generate_pin, generate_pronounceable, pin_entropy, pronounceable_entropy};
+use crate::PassMode;
+use anyhow::{anyhow, Result};
 use rand::prelude::SliceRandom;
+use rand::Rng;
 
-const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
-const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
-const NUMBER: &[u8] = b"123456789";
-const SYMBOL: &[u8] = b"!@#$%^&*_";
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+pub(super) const NUMBER: &str = "0123456789";
+pub(super) const SYMBOL: &str = "!@#$%^&*_";
 
-pub fn process_genpass(
-    length: u8,
-    upper: bool,
-    lower: bool,
-    number: bool,
-    symbol: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
-    let mut pass = Vec::new();
-    let mut chars = Vec::new();
+/// The longest password generated, enough for API keys and long seeds.
+pub const MAX_LENGTH: usize = 4096;
 
-    if upper {
-        chars.extend_from_slice(UPPER);
-        pass.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+/// Characters left out by default because they are easily confused with each other.
+pub const AMBIGUOUS: &str = "IOl0";
+
+/// What a generated password is made of.
+#[derive(Debug, Clone)]
+pub struct PassConfig {
+    pub mode: PassMode,
+    pub length: usize,
+    pub upper: bool,
+    pub lower: bool,
+    pub number: bool,
+    pub symbol: bool,
+    /// Replaces the default symbol set
+    pub symbols: Option<String>,
+    /// An explicit alphabet, used instead of the classes above
+    pub charset: Option<String>,
+    /// Characters never used
+    pub exclude: String,
+    /// Characters left out of the classes, but not of an explicit `charset`
+    pub ambiguous: String,
+    pub min_upper: usize,
+    pub min_lower: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+}
+
+impl Default for PassConfig {
+    fn default() -> Self {
+        Self {
+            mode: PassMode::Random,
+            length: 16,
+            upper: true,
+            lower: true,
+            number: true,
+            symbol: true,
+            symbols: None,
+            charset: None,
+            exclude: String::new(),
+            ambiguous: AMBIGUOUS.to_string(),
+            min_upper: 0,
+            min_lower: 0,
+            min_digits: 0,
+            min_symbols: 0,
+        }
     }
+}
+
+/// A class of characters a password must contain a minimum number of.
+struct Class {
+    name: &'static str,
+    chars: Vec<char>,
+    min: usize,
+}
 
-    if lower {
-        chars.extend_from_slice(LOWER);
-        pass.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+impl PassConfig {
+    /// The full alphabet and the per-class minimums, checked for satisfiability.
+    fn classes(&self) -> Result<(Vec<char>, Vec<Class>)> {
+        let keep = |c: &char| !self.exclude.contains(*c);
+        let symbols = self.symbols.as_deref().unwrap_or(SYMBOL);
+        let mut alphabet = Vec::new();
+        let mut classes = Vec::new();
+        let specs = [
+            ("uppercase letters", self.upper, UPPER, self.min_upper),
+            ("lowercase letters", self.lower, LOWER, self.min_lower),
+            ("digits", self.number, NUMBER, self.min_digits),
+            ("symbols", self.symbol, symbols, self.min_symbols),
+        ];
+        match &self.charset {
+            Some(charset) => {
+                alphabet.extend(charset.chars().filter(keep));
+                alphabet.sort_unstable();
+                alphabet.dedup();
+                for (name, _, _, min) in specs {
+                    // an explicit alphabet only requires the classes asked for
+                    let chars = alphabet
+                        .iter()
+                        .copied()
+                        .filter(|c| class_of(*c) == name)
+                        .collect();
+                    classes.push(Class { name, chars, min });
+                }
+            }
+            None => {
+                for (name, enabled, chars, min) in specs {
+                    let chars = chars
+                        .chars()
+                        .filter(keep)
+                        .filter(|c| !self.ambiguous.contains(*c))
+                        .collect::<Vec<_>>();
+                    if enabled {
+                        alphabet.extend(&chars);
+                        classes.push(Class {
+                            name,
+                            chars,
+                            min: min.max(1),
+                        });
+                    } else if min > 0 {
+                        return Err(anyhow!(
+                            "At least {} {} required, but they are disabled",
+                            min,
+                            name
+                        ));
+                    }
+                }
+            }
+        }
+        if alphabet.is_empty() {
+            return Err(anyhow!("No characters left to generate a password from"));
+        }
+        if let Some(class) = classes.iter().find(|c| c.min > 0 && c.chars.is_empty()) {
+            return Err(anyhow!(
+                "No {} left to satisfy the minimum of {}",
+                class.name,
+                class.min
+            ));
+        }
+        let required = classes.iter().map(|c| c.min).sum::<usize>();
+        if required > self.length {
+            return Err(anyhow!(
+                "Length {} is too short for the {} required characters",
+                self.length,
+                required
+            ));
+        }
+        Ok((alphabet, classes))
     }
 
-    if number {
-        chars.extend_from_slice(NUMBER);
-        pass.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+    fn check_length(&self) -> Result<()> {
+        if !(1..=MAX_LENGTH).contains(&self.length) {
+            return Err(anyhow!(
+                "Length {} is out of range, it must be between 1 and {}",
+                self.length,
+                MAX_LENGTH
+            ));
+        }
+        Ok(())
     }
 
-    if symbol {
-        chars.extend_from_slice(SYMBOL);
-        pass.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+    /// Bits of entropy of a generated password.
+    pub fn entropy(&self) -> Result<f64> {
+        self.check_length()?;
+        match self.mode {
+            PassMode::Random => {
+                let (alphabet, _) = self.classes()?;
+                Ok(self.length as f64 * (alphabet.len() as f64).log2())
+            }
+            PassMode::Pronounceable => pronounceable_entropy(self),
+            PassMode::Pin => pin_entropy(self),
+        }
     }
+}
 
-    for _ in 0..(length - pass.len() as u8) {
-        let c = chars
-            .choose(&mut rng)
-            .expect("chars won't be empty in this context");
-        pass.push(*c);
+fn class_of(c: char) -> &'static str {
+    if c.is_uppercase() {
+        "uppercase letters"
+    } else if c.is_lowercase() {
+        "lowercase letters"
+    } else if c.is_numeric() {
+        "digits"
+    } else {
+        "symbols"
     }
+}
 
-    pass.shuffle(&mut rng);
+/// Generates a password with the thread-local random generator.
+pub fn process_genpass(config: &PassConfig) -> Result<String> {
+    generate_password(config, &mut rand::thread_rng())
+}
 
-    // let pass = String::from_utf8(pass)?;
-    // println!("{}", pass);
+/// Generates a password in the configured mode.
+pub fn generate_password<R: Rng + ?Sized>(config: &PassConfig, rng: &mut R) -> Result<String> {
+    config.check_length()?;
+    match config.mode {
+        PassMode::Random => generate_random(config, rng),
+        PassMode::Pronounceable => generate_pronounceable(config, rng),
+        PassMode::Pin => generate_pin(config, rng),
+    }
+}
 
-    Ok(String::from_utf8(pass)?)
+/// The class minimums first, then random characters from the whole alphabet, shuffled.
+fn generate_random<R: Rng + ?Sized>(config: &PassConfig, rng: &mut R) -> Result<String> {
+    let (alphabet, classes) = config.classes()?;
+    let mut pass = Vec::with_capacity(config.length);
+    for class in &classes {
+        for _ in 0..class.min {
+            pass.push(*class.chars.choose(rng).expect("checked in classes()"));
+        }
+    }
+    while pass.len() < config.length {
+        pass.push(*alphabet.choose(rng).expect("checked in classes()"));
+    }
+    pass.shuffle(rng);
+    Ok(pass.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_generate_password() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let pass = generate_password(&PassConfig::default(), &mut rng)?;
+        assert_eq!(pass.len(), 16);
+        assert!(!pass.contains(|c| AMBIGUOUS.contains(c)));
+        assert_eq!(
+            pass,
+            generate_password(&PassConfig::default(), &mut StdRng::seed_from_u64(7))?
+        );
+
+        let config = PassConfig {
+            length: 12,
+            symbols: Some("-+".into()),
+            exclude: "abc".into(),
+            ambiguous: String::new(),
+            min_digits: 4,
+            min_symbols: 2,
+            ..Default::default()
+        };
+        for _ in 0..50 {
+            let pass = generate_password(&config, &mut rng)?;
+            assert!(pass.chars().filter(char::is_ascii_digit).count() >= 4);
+            assert!(pass.chars().filter(|c| "-+".contains(*c)).count() >= 2);
+            assert!(!pass.contains(['a', 'b', 'c', '!']));
+        }
+
+        let config = PassConfig {
+            charset: Some("ab01".into()),
+            min_digits: 2,
+            ..Default::default()
+        };
+        let pass = generate_password(&config, &mut rng)?;
+        assert!(pass.chars().all(|c| "ab01".contains(c)));
+        assert!(pass.chars().filter(char::is_ascii_digit).count() >= 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsatisfiable_config() {
+        let short = PassConfig {
+            length: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            process_genpass(&short).unwrap_err().to_string(),
+            "Length 3 is too short for the 4 required characters"
+        );
+        let no_digits = PassConfig {
+            number: false,
+            min_digits: 1,
+            ..Default::default()
+        };
+        assert!(process_genpass(&no_digits).is_err());
+        let excluded = PassConfig {
+            charset: Some("abc".into()),
+            exclude: "abc".into(),
+            ..Default::default()
+        };
+        assert!(process_genpass(&excluded).is_err());
+        for length in [0, MAX_LENGTH + 1] {
+            let config = PassConfig {
+                length,
+                ..Default::default()
+            };
+            assert!(process_genpass(&config).is_err());
+        }
+        let long = PassConfig {
+            length: 512,
+            ..Default::default()
+        };
+        assert_eq!(process_genpass(&long).map(|p| p.len()).ok(), Some(512));
+    }
 }
@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Context, Result};
+use rand::prelude::SliceRandom;
+use rand::Rng;
+
+/// The embedded wordlist, for now BIP-39 English (CC0): 2048 words, 11 bits each. It stands
+/// in for the EFF large list, which can replace the file as is; see `assets/README.md`.
+const WORDLIST: &str = include_str!("../../assets/wordlist.txt");
+
+/// Loads the distinct words of a `wordlist` file, or of the embedded list without one.
+///
+/// The file has one word per line; diceware lists with leading dice numbers work too.
+pub fn load_wordlist(wordlist: Option<&str>) -> Result<Vec<String>> {
+    let content = match wordlist {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read wordlist {}", path))?,
+        None => WORDLIST.to_string(),
+    };
+    let list = parse_wordlist(&content);
+    if list.len() < 2 {
+        return Err(anyhow!("The wordlist needs at least 2 distinct words"));
+    }
+    Ok(list.into_iter().map(String::from).collect())
+}
+
+/// Generates a passphrase of `words` random words from `list`, as loaded by
+/// `load_wordlist`, and returns it with its entropy in bits.
+///
+/// With `digit`, a random digit is appended to one random word.
+pub fn process_genphrase<R: Rng + ?Sized>(
+    list: &[String],
+    words: usize,
+    separator: &str,
+    capitalize: bool,
+    digit: bool,
+    rng: &mut R,
+) -> Result<(String, f64)> {
+    if words == 0 {
+        return Err(anyhow!("A passphrase needs at least one word"));
+    }
+    if list.len() < 2 {
+        return Err(anyhow!("The wordlist needs at least 2 distinct words"));
+    }
+
+    let mut phrase = (0..words)
+        .map(|_| {
+            let word = list.choose(rng).expect("list won't be empty");
+            if capitalize {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.clone()
+            }
+        })
+        .collect::<Vec<String>>();
+
+    let mut entropy = words as f64 * (list.len() as f64).log2();
+    if digit {
+        let i = rng.gen_range(0..words);
+        phrase[i].push(char::from(b'0' + rng.gen_range(0..10)));
+        entropy += 10f64.log2() + (words as f64).log2();
+    }
+    Ok((phrase.join(separator), entropy))
+}
+
+fn parse_wordlist(content: &str) -> Vec<&str> {
+    let mut list = content
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .collect::<Vec<_>>();
+    // duplicates would make some words likelier and the entropy estimate wrong
+    list.sort_unstable();
+    list.dedup();
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_process_genphrase() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(7);
+        // holds for the BIP-39 list as well as the EFF large list
+        let list = load_wordlist(None)?;
+        assert!(list.len() >= 2048);
+
+        let (phrase, entropy) = process_genphrase(&list, 6, "-", false, false, &mut rng)?;
+        assert_eq!(phrase.split('-').count(), 6);
+        assert_eq!(entropy, 6.0 * (list.len() as f64).log2());
+        let again = process_genphrase(&list, 6, "-", false, false, &mut StdRng::seed_from_u64(7))?;
+        assert_eq!(again.0, phrase);
+
+        let (phrase, entropy) = process_genphrase(&list, 4, " ", true, true, &mut rng)?;
+        let words = phrase.split(' ').collect::<Vec<_>>();
+        assert!(words
+            .iter()
+            .all(|w| w.starts_with(|c: char| c.is_uppercase())));
+        assert_eq!(phrase.chars().filter(char::is_ascii_digit).count(), 1);
+        assert!(entropy > 44.0 + 3.3 + 2.0 - 0.1);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "11111\tcorrect\n11112\thorse\n\n11113 horse\n")?;
+        let words = load_wordlist(path.to_str())?;
+        assert_eq!(words, ["correct", "horse"]);
+        let (phrase, entropy) = process_genphrase(&words, 3, ".", false, false, &mut rng)?;
+        assert!(phrase.split('.').all(|w| w == "correct" || w == "horse"));
+        assert_eq!(entropy, 3.0);
+
+        std::fs::write(&path, "only\n")?;
+        assert!(load_wordlist(path.to_str()).is_err());
+        assert!(process_genphrase(&list, 0, "-", false, false, &mut rng).is_err());
+        Ok(())
+    }
+}
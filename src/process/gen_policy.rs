@@ -0,0 +1,233 @@
+use super::gen_pass::{generate_password, PassConfig};
+use anyhow::{anyhow, Context, Result};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use zxcvbn::zxcvbn;
+
+const MAX_ATTEMPTS: usize = 1000;
+
+/// A named password policy, e.g. in `policies.yaml`:
+///
+/// ```yaml
+/// corp-ad:
+///   length: 14
+///   classes: [upper, lower, digits, symbols]
+///   min_digits: 2
+///   forbidden: [password, qwerty, "123"]
+///   max_repeat: 2
+///   min_score: 3
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub length: Option<usize>,
+    /// The classes passwords are drawn from, each required at least once
+    pub classes: Option<Vec<CharClass>>,
+    pub min_upper: Option<usize>,
+    pub min_lower: Option<usize>,
+    pub min_digits: Option<usize>,
+    pub min_symbols: Option<usize>,
+    pub symbols: Option<String>,
+    pub exclude: Option<String>,
+    /// Sequences that must not appear, ignoring case
+    #[serde(default)]
+    pub forbidden: Vec<String>,
+    /// The longest allowed run of one repeated character
+    pub max_repeat: Option<usize>,
+    /// The minimum zxcvbn score, 0 to 4
+    pub min_score: Option<u8>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Upper,
+    Lower,
+    Digits,
+    Symbols,
+}
+
+/// Loads a policy by name from a YAML or TOML (by extension) file of named policies.
+pub fn load_policy(path: &str, name: &str) -> Result<Policy> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read policy file {}", path))?;
+    let mut policies: HashMap<String, Policy> = if path.ends_with(".toml") {
+        toml::from_str(&content).with_context(|| format!("Invalid policy file {}", path))?
+    } else {
+        serde_yaml::from_str(&content).with_context(|| format!("Invalid policy file {}", path))?
+    };
+    policies
+        .remove(name)
+        .ok_or_else(|| anyhow!("Policy {:?} not found in {}", name, path))
+}
+
+impl Policy {
+    /// Overrides the parts of `config` the policy sets.
+    pub fn apply(&self, config: &mut PassConfig) {
+        if let Some(length) = self.length {
+            config.length = length;
+        }
+        if let Some(classes) = &self.classes {
+            config.upper = classes.contains(&CharClass::Upper);
+            config.lower = classes.contains(&CharClass::Lower);
+            config.number = classes.contains(&CharClass::Digits);
+            config.symbol = classes.contains(&CharClass::Symbols);
+        }
+        let mins = [
+            (self.min_upper, &mut config.min_upper),
+            (self.min_lower, &mut config.min_lower),
+            (self.min_digits, &mut config.min_digits),
+            (self.min_symbols, &mut config.min_symbols),
+        ];
+        for (min, field) in mins {
+            if let Some(min) = min {
+                *field = min;
+            }
+        }
+        if self.symbols.is_some() {
+            config.symbols.clone_from(&self.symbols);
+        }
+        if let Some(exclude) = &self.exclude {
+            config.exclude.push_str(exclude);
+        }
+    }
+
+    /// Checks a password against the rules that generation alone cannot guarantee.
+    pub fn check(&self, pass: &str) -> Result<()> {
+        let lower = pass.to_lowercase();
+        if let Some(seq) = self
+            .forbidden
+            .iter()
+            .find(|seq| lower.contains(&seq.to_lowercase()))
+        {
+            return Err(anyhow!("contains the forbidden sequence {:?}", seq));
+        }
+        if let Some(max) = self.max_repeat {
+            let chars = pass.chars().collect::<Vec<_>>();
+            let longest = chars
+                .chunk_by(|a, b| a == b)
+                .map(<[char]>::len)
+                .max()
+                .unwrap_or_default();
+            if longest > max {
+                return Err(anyhow!("repeats a character {} times in a row", longest));
+            }
+        }
+        if let Some(min) = self.min_score {
+            let score = zxcvbn(pass, &[])?.score();
+            if score < min {
+                return Err(anyhow!("has a strength score of {}, below {}", score, min));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.min_score.is_some_and(|s| s > 4) {
+            return Err(anyhow!("min_score must be between 0 and 4"));
+        }
+        if self.max_repeat == Some(0) {
+            return Err(anyhow!("max_repeat must be at least 1"));
+        }
+        if self.forbidden.iter().any(String::is_empty) {
+            return Err(anyhow!("forbidden sequences must not be empty"));
+        }
+        Ok(())
+    }
+}
+
+/// Generates passwords until one satisfies the policy, failing with the reason when the
+/// policy is unsatisfiable or no candidate passed within a bounded number of attempts.
+pub fn generate_with_policy<R: Rng + ?Sized>(
+    config: &PassConfig,
+    policy: &Policy,
+    rng: &mut R,
+) -> Result<String> {
+    policy
+        .validate()
+        .and_then(|_| generate_password(config, rng).map(|_| ()))
+        .context("The policy cannot be satisfied")?;
+    let mut reason = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let pass = generate_password(config, rng)?;
+        match policy.check(&pass) {
+            Ok(()) => return Ok(pass),
+            Err(e) => reason = Some(e),
+        }
+    }
+    Err(anyhow!(
+        "No password satisfied the policy in {} attempts, the last one {}",
+        MAX_ATTEMPTS,
+        reason.expect("at least one attempt")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_load_policy() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let yaml = dir.path().join("policies.yaml");
+        std::fs::write(
+            &yaml,
+            "corp-ad:\n  length: 14\n  classes: [upper, lower, digits]\n  min_digits: 3\n",
+        )?;
+        let toml = dir.path().join("policies.toml");
+        std::fs::write(&toml, "[corp-ad]\nlength = 14\nclasses = [\"upper\", \"lower\", \"digits\"]\nmin_digits = 3\n")?;
+
+        for path in [yaml, toml] {
+            let policy = load_policy(path.to_str().unwrap(), "corp-ad")?;
+            let mut config = PassConfig::default();
+            policy.apply(&mut config);
+            assert_eq!(
+                (config.length, config.symbol, config.min_digits),
+                (14, false, 3)
+            );
+            assert!(load_policy(path.to_str().unwrap(), "wifi").is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_with_policy() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let policy = Policy {
+            forbidden: vec!["a".into(), "E".into()],
+            max_repeat: Some(1),
+            min_score: Some(3),
+            ..Default::default()
+        };
+        let pass = generate_with_policy(&PassConfig::default(), &policy, &mut rng)?;
+        assert!(!pass.contains(['a', 'A', 'e', 'E']));
+        assert!(pass.as_bytes().windows(2).all(|w| w[0] != w[1]));
+
+        let policy = Policy {
+            length: Some(3),
+            ..Default::default()
+        };
+        let mut config = PassConfig::default();
+        policy.apply(&mut config);
+        let err = generate_with_policy(&config, &policy, &mut rng).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "The policy cannot be satisfied: Length 3 is too short for the 4 required characters"
+        );
+
+        let policy = Policy {
+            forbidden: vec!["a".into(), "b".into()],
+            ..Default::default()
+        };
+        let config = PassConfig {
+            charset: Some("ab".into()),
+            ..Default::default()
+        };
+        let err = generate_with_policy(&config, &policy, &mut rng).unwrap_err();
+        assert!(err.to_string().contains("forbidden sequence"));
+        Ok(())
+    }
+}
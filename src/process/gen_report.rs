@@ -0,0 +1,120 @@
+use super::breach::{generate_unbreached, BreachDb};
+use super::gen_pass::{generate_password, PassConfig};
+use super::gen_policy::{generate_with_policy, Policy};
+use crate::PassFormat;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use zxcvbn::zxcvbn;
+
+/// A generated password with its strength estimates.
+#[derive(Debug, Serialize)]
+pub struct PassReport {
+    pub password: String,
+    /// The zxcvbn score, 0 to 4
+    pub score: u8,
+    /// zxcvbn's crack time estimate against a slow hash at 10k guesses per second
+    pub crack_time: String,
+    /// Bits of entropy of the generator
+    pub entropy: f64,
+}
+
+impl PassReport {
+    pub fn new(password: String, entropy: f64) -> Result<Self> {
+        let estimate = zxcvbn(&password, &[])?;
+        Ok(Self {
+            crack_time: estimate
+                .crack_times()
+                .offline_slow_hashing_1e4_per_second()
+                .to_string(),
+            score: estimate.score(),
+            entropy: (entropy * 10.0).round() / 10.0,
+            password,
+        })
+    }
+}
+
+impl AsRef<str> for PassReport {
+    fn as_ref(&self) -> &str {
+        &self.password
+    }
+}
+
+/// Generates `count` passwords, regenerating each until it satisfies `policy` and is not in
+/// `breach_db` if given.
+pub fn process_genpass_batch(
+    mut config: PassConfig,
+    policy: Option<&Policy>,
+    breach_db: Option<&BreachDb>,
+    count: usize,
+) -> Result<Vec<PassReport>> {
+    if let Some(policy) = policy {
+        policy.apply(&mut config);
+    }
+    let entropy = config.entropy()?;
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|_| {
+            generate_unbreached(breach_db, || match policy {
+                Some(policy) => generate_with_policy(&config, policy, &mut rng),
+                None => generate_password(&config, &mut rng),
+            })
+            .and_then(|pass| PassReport::new(pass, entropy))
+        })
+        .collect()
+}
+
+/// Writes passwords one per line, or with their estimates as JSON or CSV.
+pub fn write_passwords(
+    reports: &[PassReport],
+    format: PassFormat,
+    mut out: impl Write,
+) -> Result<()> {
+    match format {
+        PassFormat::Plain => {
+            for report in reports {
+                writeln!(out, "{}", report.password)?;
+            }
+        }
+        PassFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, reports)?;
+            writeln!(out)?;
+        }
+        PassFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for report in reports {
+                writer.serialize(report)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_passwords() -> Result<()> {
+        let reports = process_genpass_batch(PassConfig::default(), None, None, 3)?;
+        assert_eq!(reports.len(), 3);
+        // 16 characters from 26 + 26 + 10 + 9 minus 4 ambiguous: 16 * log2(67)
+        assert_eq!(reports[0].entropy, 97.1);
+
+        let report = PassReport::new("password".into(), 37.6)?;
+        let mut out = Vec::new();
+        write_passwords(&[report], PassFormat::Csv, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "password,score,crack_time,entropy\npassword,0,less than a second,37.6\n"
+        );
+
+        let mut out = Vec::new();
+        write_passwords(&reports, PassFormat::Json, &mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value.as_array().map(Vec::len), Some(3));
+        assert!(value[0]["score"].is_u64());
+        Ok(())
+    }
+}
@@ -1,15 +1,48 @@
 mod b64;
+mod breach;
 mod csv_convert;
+mod csv_diff;
+mod csv_encoding;
+mod csv_filter;
+mod csv_nest;
+mod csv_ops;
+mod csv_query;
+mod csv_show;
+mod csv_sql;
+mod csv_stats;
+mod csv_types;
+mod csv_validate;
+mod gen_bytes;
+mod gen_derive;
+mod gen_modes;
 mod gen_pass;
+mod gen_phrase;
+mod gen_policy;
+mod gen_report;
 mod http_serve;
 mod jwt;
+mod pass_check;
 mod text;
 
 pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+pub use breach::{generate_unbreached, process_breach_build, BreachDb};
+pub use csv_convert::{process_csv, process_csv_import};
+pub use csv_diff::{process_csv_diff, write_diff, CellChange, CsvDiff, RowChange};
+pub use csv_filter::{Predicate, SortKey};
+pub use csv_ops::{process_csv_cat, process_csv_dedupe, process_csv_join, process_csv_split};
+pub use csv_query::{process_csv_query, query_csv, QueryResult};
+pub use csv_show::{process_csv_show, RowWindow, TableLayout};
+pub use csv_stats::{process_csv_stats, write_stats, ColumnStats, TopValue};
+pub use csv_validate::{process_csv_validate, write_validation, ValidationReport, Violation};
+pub use gen_bytes::{process_genbytes, MAX_BYTES};
+pub use gen_derive::{process_genpass_derive, read_master_secret};
+pub use gen_pass::{generate_password, process_genpass, PassConfig, AMBIGUOUS, MAX_LENGTH};
+pub use gen_phrase::{load_wordlist, process_genphrase};
+pub use gen_policy::{generate_with_policy, load_policy, Policy};
+pub use gen_report::{process_genpass_batch, write_passwords, PassReport};
 pub use http_serve::process_http_serve;
 pub use jwt::{process_jwt_sign, process_jwt_verify};
+pub use pass_check::{process_passcheck, write_passcheck, CrackTimes, PassCheck, PatternMatch};
 pub use text::{
     process_text_decrypt, process_text_encrypt, process_text_keygen, process_text_sign,
     process_text_verify,
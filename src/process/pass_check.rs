@@ -0,0 +1,216 @@
+use super::breach::BreachDb;
+use crate::{get_reader, OutputFormat};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use zxcvbn::matching::patterns::MatchPattern;
+use zxcvbn::zxcvbn;
+
+/// zxcvbn's assessment of one password.
+#[derive(Debug, Serialize)]
+pub struct PassCheck {
+    /// The 1-based input line the password came from
+    pub line: usize,
+    /// 0 to 4
+    pub score: u8,
+    pub guesses_log10: f64,
+    pub crack_times: CrackTimes,
+    /// How zxcvbn would guess the password, piece by piece
+    pub patterns: Vec<PatternMatch>,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    /// How often the password appears in the breach db, if one was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breached: Option<u32>,
+}
+
+/// Estimated crack times for a few attack scenarios.
+#[derive(Debug, Serialize)]
+pub struct CrackTimes {
+    pub online_throttled: String,
+    pub online: String,
+    pub offline_slow_hash: String,
+    pub offline_fast_hash: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternMatch {
+    pub pattern: &'static str,
+    pub token: String,
+    /// The dictionary the token was found in, for dictionary matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+}
+
+impl PassCheck {
+    pub fn new(password: &str, line: usize, user_inputs: &[&str]) -> Result<Self> {
+        let estimate = zxcvbn(password, user_inputs)?;
+        let times = estimate.crack_times();
+        let feedback = estimate.feedback().as_ref();
+        Ok(Self {
+            line,
+            score: estimate.score(),
+            guesses_log10: (estimate.guesses_log10() * 100.0).round() / 100.0,
+            crack_times: CrackTimes {
+                online_throttled: times.online_throttling_100_per_hour().to_string(),
+                online: times.online_no_throttling_10_per_second().to_string(),
+                offline_slow_hash: times.offline_slow_hashing_1e4_per_second().to_string(),
+                offline_fast_hash: times.offline_fast_hashing_1e10_per_second().to_string(),
+            },
+            patterns: estimate
+                .sequence()
+                .iter()
+                .map(|m| {
+                    let (pattern, dictionary) = match &m.pattern {
+                        MatchPattern::Dictionary(d) => {
+                            ("dictionary", Some(format!("{:?}", d.dictionary_name)))
+                        }
+                        MatchPattern::Spatial(_) => ("spatial", None),
+                        MatchPattern::Repeat(_) => ("repeat", None),
+                        MatchPattern::Sequence(_) => ("sequence", None),
+                        MatchPattern::Regex(_) => ("regex", None),
+                        MatchPattern::Date(_) => ("date", None),
+                        MatchPattern::BruteForce => ("bruteforce", None),
+                    };
+                    PatternMatch {
+                        pattern,
+                        token: m.token.clone(),
+                        dictionary,
+                    }
+                })
+                .collect(),
+            warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+            suggestions: feedback
+                .map(|f| f.suggestions().iter().map(ToString::to_string).collect())
+                .unwrap_or_default(),
+            breached: None,
+        })
+    }
+}
+
+/// Checks each non-empty line of `input`, or a password read from a no-echo prompt when
+/// `input` is `-` and stdin is a terminal. `user_inputs` are words like names or the site
+/// that make a password weaker if it contains them. With `breach_db`, each password is also
+/// looked up in it.
+pub fn process_passcheck(
+    input: &str,
+    user_inputs: &[String],
+    breach_db: Option<&str>,
+) -> Result<Vec<PassCheck>> {
+    let user_inputs = user_inputs.iter().map(String::as_str).collect::<Vec<_>>();
+    let db = breach_db.map(BreachDb::open).transpose()?;
+    let check = |password: &str, line| -> Result<PassCheck> {
+        let mut check = PassCheck::new(password, line, &user_inputs)?;
+        check.breached = db.as_ref().map(|db| db.count(password)).transpose()?;
+        Ok(check)
+    };
+    if input == "-" && io::stdin().is_terminal() {
+        let password = rpassword::prompt_password("Password: ")?;
+        if password.is_empty() {
+            return Err(anyhow!("No password given"));
+        }
+        return Ok(vec![check(&password, 1)?]);
+    }
+    let mut checks = Vec::new();
+    for (i, line) in BufReader::new(get_reader(input)?).lines().enumerate() {
+        let line = line?;
+        let password = line.strip_suffix('\r').unwrap_or(&line);
+        if !password.is_empty() {
+            checks.push(check(password, i + 1)?);
+        }
+    }
+    Ok(checks)
+}
+
+pub fn write_passcheck(
+    checks: &[PassCheck],
+    format: Option<OutputFormat>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        None => {
+            for check in checks {
+                writeln!(out, "line {}: score {}/4", check.line, check.score)?;
+                writeln!(out, "  guesses: 10^{}", check.guesses_log10)?;
+                let times = &check.crack_times;
+                writeln!(out, "  crack time:")?;
+                writeln!(out, "    online, throttled: {}", times.online_throttled)?;
+                writeln!(out, "    online: {}", times.online)?;
+                writeln!(out, "    offline, slow hash: {}", times.offline_slow_hash)?;
+                writeln!(out, "    offline, fast hash: {}", times.offline_fast_hash)?;
+                let patterns = check
+                    .patterns
+                    .iter()
+                    .map(|p| match &p.dictionary {
+                        Some(dict) => format!("{} {:?} ({})", p.pattern, p.token, dict),
+                        None => format!("{} {:?}", p.pattern, p.token),
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(out, "  patterns: {}", patterns.join(", "))?;
+                if let Some(warning) = &check.warning {
+                    writeln!(out, "  warning: {}", warning)?;
+                }
+                for suggestion in &check.suggestions {
+                    writeln!(out, "  suggestion: {}", suggestion)?;
+                }
+                match check.breached {
+                    Some(0) => writeln!(out, "  breached: no")?,
+                    Some(n) => writeln!(out, "  breached: seen {} times", n)?,
+                    None => {}
+                }
+            }
+        }
+        Some(OutputFormat::Json) => {
+            serde_json::to_writer_pretty(&mut *out, checks)?;
+            writeln!(out)?;
+        }
+        Some(OutputFormat::Yaml) => serde_yaml::to_writer(&mut *out, checks)?,
+        Some(f) => {
+            return Err(anyhow!(
+                "Cannot write a password check as {}, use json or yaml",
+                f
+            ))
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_passcheck() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("passwords.txt");
+        std::fs::write(
+            &path,
+            "password\n\nrustacean-2024\r\ncorrect horse battery staple\n",
+        )?;
+        let checks = process_passcheck(path.to_str().unwrap(), &["rustacean".into()], None)?;
+        assert_eq!(
+            checks.iter().map(|c| c.line).collect::<Vec<_>>(),
+            vec![1, 3, 4]
+        );
+        assert_eq!(checks[0].score, 0);
+        assert!(checks[0].warning.is_some());
+        assert_eq!(checks[0].patterns[0].pattern, "dictionary");
+        assert_eq!(
+            checks[0].patterns[0].dictionary.as_deref(),
+            Some("Passwords")
+        );
+        assert!(checks[1]
+            .patterns
+            .iter()
+            .any(|p| p.dictionary.as_deref() == Some("UserInputs") && p.token == "rustacean"));
+        assert!(checks[2].score >= 3);
+
+        let mut out = Vec::new();
+        write_passcheck(&checks[..1], None, &mut out)?;
+        let text = String::from_utf8(out)?;
+        assert!(text.starts_with("line 1: score 0/4\n"));
+        assert!(text.contains("patterns: dictionary \"password\" (Passwords)"));
+        Ok(())
+    }
+}
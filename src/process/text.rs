@@ -1,4 +1,4 @@
-use crate::{process_genpass, TextSignFormat};
+use crate::{process_genpass, PassConfig, TextSignFormat};
 use anyhow::Result;
 use chacha20poly1305::aead::generic_array::typenum::Unsigned;
 use chacha20poly1305::aead::generic_array::GenericArray;
@@ -54,7 +54,11 @@ impl Blake3 {
     }
 
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
-        let key = process_genpass(32, true, true, true, true)?;
+        let config = PassConfig {
+            length: 32,
+            ..Default::default()
+        };
+        let key = process_genpass(&config)?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
         Ok(map)
@@ -1,5 +1,8 @@
 use anyhow::Result;
-use std::{fs::File, io::Read};
+use std::{
+    fs::File,
+    io::{Read, Write},
+};
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     if input == "-" {
@@ -10,6 +13,15 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     }
 }
 
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    if output == "-" {
+        Ok(Box::new(std::io::stdout()))
+    } else {
+        let file = File::create(output)?;
+        Ok(Box::new(file))
+    }
+}
+
 pub fn get_content(input: &str) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();